@@ -1,67 +1,41 @@
+use crate::actors::layers;
+use crate::engine::assets::SpriteHandle;
 use crate::engine::collision_2d::Collision2D;
+use crate::engine::ecs::components::DespawnOutside;
+use crate::engine::ecs::{EntityId, World};
 use crate::engine::entity::Entity;
-use crate::engine::draw::Draw;
-use wgpu::Surface;
-use wgpu::Queue;
-use wgpu::Device;
-use wgpu::Adapter;
-use wgpu::SurfaceConfiguration;
-use std::time::Duration;
 use cgmath::Vector2;
 
-pub struct Bullet {
-    entity: Entity,
-    collision: Collision2D,
-    alive: bool,
-}
-
-impl Bullet {
-    pub fn new(filepath: &str,
-        x: u32,
-        y: u32,
-        rotation: f32,
-        scale: f32,
-        surface: &Surface,
-        config: &SurfaceConfiguration,
-        adapter: &wgpu::Adapter,
-        queue: &wgpu::Queue,
-        device: &wgpu::Device,) -> Self {
-        let entity = Entity::new(filepath, x, y, rotation, scale, surface, config, adapter, queue, device);
-        let collision = Collision2D::new(entity.position, entity.sprite.diffuse_texture.texture.width() as f32, entity.sprite.diffuse_texture.texture.height() as f32, scale);
-        Bullet {
-            entity,
-            collision,
-            alive: true,
-        }
-    }
-
-    pub fn update(&mut self, time_elapsed: &Duration, screen_height: f32) {
-        if self.entity.position.y > screen_height {
-            self.alive = false;
-        }
-        let y = self.entity.position.y + (4000.0 * time_elapsed.as_secs_f64()) as f32;
-        self.entity.update(self.entity.position_x(), y as u32, self.entity.rotation(), self.entity.scale());
-        self.collision.update(self.entity.position);
-    }
+// Bullets go under everything else.
+const DRAW_LAYER: i32 = 0;
 
-    pub fn get_collision(&self) -> &Collision2D {
-        &self.collision
-    }
-
-    pub fn kill(&mut self) {
-        self.alive = false;
-    }
-
-    pub fn alive(&self) -> bool {
-        self.alive
-    }
-}
+// Marks the player's shots.
+pub struct Bullet;
 
-impl Draw for Bullet {
-    fn draw(&self,
-        device: &wgpu::Device,
-        encoder: &mut wgpu::CommandEncoder,
-        view: &wgpu::TextureView) -> Result<(), wgpu::SurfaceError> {
-            self.entity.render(device, encoder, view)
-        }
+// Flies in a straight line until it's off the screen. angle is in degrees clockwise from
+// straight up, speed in pixels per second.
+pub fn spawn(world: &mut World,
+    sprite: SpriteHandle,
+    position: Vector2<f32>,
+    angle: f32,
+    speed: f32,
+    scale: f32,
+    screen: Vector2<f32>) -> EntityId {
+    let mut entity = Entity::new(sprite, position, angle, scale);
+    let (sin, cos) = angle.to_radians().sin_cos();
+    entity.velocity = Vector2 { x: sin, y: cos } * speed;
+    entity.set_layer(DRAW_LAYER);
+    let mut collision = Collision2D::new(entity.size(), &entity.matrix());
+    collision.set_layers(layers::PLAYER_BULLET, layers::ENEMY);
+    // all the way off the screen, not just its middle
+    let margin = collision.width().max(collision.height());
+    let id = world.spawn();
+    world.insert(id, entity);
+    world.insert(id, collision);
+    world.insert(id, Bullet);
+    world.insert(id, DespawnOutside {
+        min: Vector2 { x: -margin, y: -margin },
+        max: Vector2 { x: screen.x + margin, y: screen.y + margin },
+    });
+    id
 }
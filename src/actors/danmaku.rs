@@ -0,0 +1,810 @@
+// Enemy bullet patterns. Emitters fire patterns from the patterns file, every bullet they
+// fire is an instance of one EntityGroup so thousands of them are still a single draw.
+use crate::actors::enemy::Enemy;
+use crate::actors::player::PlayerControl;
+use crate::actors::{layers, player_hit, GRID_CELL_SIZE};
+use crate::engine::collision_2d::{Collider, Collision2D, SpatialGrid};
+use crate::engine::config;
+use crate::engine::context::Context;
+use crate::engine::draw::Draw;
+use crate::engine::ecs::{EntityId, World};
+use crate::engine::entity::Entity;
+use crate::engine::entity_group::EntityGroup;
+use crate::engine::error::Result;
+use crate::engine::gpu::Gpu;
+use crate::engine::sprite_batch::SpriteBatch;
+use cgmath::Vector2;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::rc::Rc;
+use std::time::Duration;
+
+pub const PATTERNS_PATH: &str = "assets/patterns.ron";
+
+// Above enemies, under the player.
+const DRAW_LAYER: i32 = 1;
+
+// Everything in the patterns file, see assets/patterns.ron.
+#[derive(Clone, Debug, Deserialize)]
+pub struct PatternsDef {
+    // every enemy bullet looks the same, they're all one group
+    pub sprite: String,
+    #[serde(default = "default_scale")]
+    pub scale: f32,
+    // radius of the round hitbox in sprite pixels before scaling
+    pub hitbox: f32,
+    pub emitters: BTreeMap<String, EmitterDef>,
+}
+
+fn default_scale() -> f32 {
+    1.0
+}
+
+// What an enemy shoots. The pattern starts over cooldown seconds after it's done.
+#[derive(Clone, Debug, Deserialize)]
+pub struct EmitterDef {
+    pub pattern: Pattern,
+    // seconds from coming on screen to the first shot
+    #[serde(default)]
+    pub delay: f32,
+    #[serde(default = "default_cooldown")]
+    pub cooldown: f32,
+    // degrees clockwise from straight up, patterns that aren't aimed are turned by it
+    #[serde(default = "default_angle")]
+    pub angle: f32,
+}
+
+fn default_cooldown() -> f32 {
+    1.0
+}
+
+fn default_angle() -> f32 {
+    180.0
+}
+
+// Ring, Fan and Aimed fire bullets, the rest fire other patterns at different times or
+// turned by different amounts. Angles are degrees clockwise.
+#[derive(Clone, Debug, Deserialize)]
+pub enum Pattern {
+    // count bullets evenly all the way around
+    Ring { count: u32, bullet: Shot },
+    // count bullets spread degrees apart at the outermost, around the emitter's angle
+    Fan { count: u32, #[serde(default)] spread: f32, bullet: Shot },
+    // a fan around the line to the player
+    Aimed { count: u32, #[serde(default)] spread: f32, bullet: Shot },
+    // a ring of arms fired times times, interval seconds apart, turning turn degrees each time
+    Spiral { arms: u32, times: u32, interval: f32, turn: f32, bullet: Shot },
+    // the pattern times times, interval seconds apart, turning turn degrees each time
+    Repeat { times: u32, interval: f32, #[serde(default)] turn: f32, pattern: Box<Pattern> },
+    // all of them at once
+    Group(Vec<Pattern>),
+}
+
+// One bullet. Speeds are pixels per second, it speeds up by acceleration every second and
+// turns angular_velocity degrees a second, clockwise.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct Shot {
+    pub speed: f32,
+    pub acceleration: f32,
+    pub max_speed: f32,
+    pub angular_velocity: f32,
+    // bursts into another pattern after a while, the bullet itself goes
+    pub split: Option<Box<Split>>,
+}
+
+impl Default for Shot {
+    fn default() -> Self {
+        Shot {
+            speed: 300.0,
+            acceleration: 0.0,
+            max_speed: f32::INFINITY,
+            angular_velocity: 0.0,
+            split: None,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct Split {
+    // seconds after being fired
+    pub after: f32,
+    // turned by the way the bullet was going
+    pub pattern: Pattern,
+}
+
+// Every emitter in the patterns file by name, with the sprite the bullets use.
+pub fn load(path: &str) -> Result<PatternsDef> {
+    let patterns: PatternsDef = config::load_ron(path)?;
+    if patterns.emitters.is_empty() {
+        return Err(config::config_error(path, "there are no emitters"));
+    }
+    for (name, emitter) in &patterns.emitters {
+        check_emitter(emitter).map_err(|message| config::config_error(path, format!("emitter {}: {}", name, message)))?;
+    }
+    Ok(patterns)
+}
+
+// Every time in the emitter has to fit in a Duration, or seconds() panics.
+fn check_emitter(emitter: &EmitterDef) -> std::result::Result<(), String> {
+    time("delay", emitter.delay)?;
+    time("cooldown", emitter.cooldown)?;
+    length(&emitter.pattern)?;
+    Ok(())
+}
+
+fn time(name: &str, seconds: f32) -> std::result::Result<Duration, String> {
+    if seconds < 0.0 {
+        return Err(format!("{} can't be negative, it's {}", name, seconds));
+    }
+    Duration::try_from_secs_f32(seconds).map_err(|_| format!("{} isn't a time, it's {}", name, seconds))
+}
+
+// How long after the pattern starts its last burst goes, checking every time in it and in
+// any splits on the way.
+fn length(pattern: &Pattern) -> std::result::Result<Duration, String> {
+    let too_long = || "the pattern goes on too long".to_string();
+    match pattern {
+        Pattern::Ring { bullet, .. } | Pattern::Fan { bullet, .. } | Pattern::Aimed { bullet, .. } => {
+            check_shot(bullet)?;
+            Ok(Duration::ZERO)
+        }
+        Pattern::Spiral { times, interval, bullet, .. } => {
+            check_shot(bullet)?;
+            let interval = time("interval", *interval)?;
+            interval.checked_mul(times.saturating_sub(1)).ok_or_else(too_long)
+        }
+        Pattern::Repeat { times, interval, pattern, .. } => {
+            let interval = time("interval", *interval)?;
+            let last = interval.checked_mul(times.saturating_sub(1)).ok_or_else(too_long)?;
+            last.checked_add(length(pattern)?).ok_or_else(too_long)
+        }
+        Pattern::Group(patterns) => patterns.iter().try_fold(Duration::ZERO, |longest, pattern| Ok(longest.max(length(pattern)?))),
+    }
+}
+
+// A split starts a timeline of its own, so its pattern's length doesn't add to this one's.
+fn check_shot(shot: &Shot) -> std::result::Result<(), String> {
+    if let Some(split) = &shot.split {
+        time("split after", split.after)?;
+        length(&split.pattern)?;
+    }
+    Ok(())
+}
+
+// Resource with the emitters new enemies pick from.
+pub struct Emitters(pub Vec<Rc<EmitterDef>>);
+
+// One Ring, Fan or Aimed out of a pattern, at the time it fires.
+#[derive(Clone, Debug)]
+struct Burst {
+    at: Duration,
+    // added up from the Spirals and Repeats it's in
+    turn: f32,
+    pattern: Pattern,
+}
+
+// A pattern flattened into the bursts it fires, in the order they go.
+#[derive(Clone, Debug)]
+struct Timeline {
+    bursts: Vec<Burst>,
+    clock: Duration,
+    next: usize,
+}
+
+impl Timeline {
+    fn new(pattern: &Pattern) -> Self {
+        let mut bursts = Vec::new();
+        flatten(pattern, Duration::ZERO, 0.0, &mut bursts);
+        // stable, so bursts at the same time keep the order they're written in
+        bursts.sort_by_key(|burst| burst.at);
+        Timeline {
+            bursts,
+            clock: Duration::ZERO,
+            next: 0,
+        }
+    }
+
+    // The bursts that came due.
+    fn advance(&mut self, dt: Duration) -> &[Burst] {
+        self.clock += dt;
+        let start = self.next;
+        while self.bursts.get(self.next).is_some_and(|burst| burst.at <= self.clock) {
+            self.next += 1;
+        }
+        &self.bursts[start..self.next]
+    }
+
+    fn is_finished(&self) -> bool {
+        self.next == self.bursts.len()
+    }
+
+    // How far the clock went past the last burst.
+    fn overshoot(&self) -> Duration {
+        let last = self.bursts.last().map_or(Duration::ZERO, |burst| burst.at);
+        self.clock.saturating_sub(last)
+    }
+
+    fn restart(&mut self) {
+        self.clock = Duration::ZERO;
+        self.next = 0;
+    }
+}
+
+fn flatten(pattern: &Pattern, at: Duration, turn: f32, bursts: &mut Vec<Burst>) {
+    match pattern {
+        Pattern::Ring { .. } | Pattern::Fan { .. } | Pattern::Aimed { .. } => bursts.push(Burst {
+            at,
+            turn,
+            pattern: pattern.clone(),
+        }),
+        Pattern::Spiral { arms, times, interval, turn: step, bullet } => {
+            let ring = Pattern::Ring {
+                count: *arms,
+                bullet: bullet.clone(),
+            };
+            for i in 0..*times {
+                flatten(&ring, at + seconds(*interval) * i, turn + step * i as f32, bursts);
+            }
+        }
+        Pattern::Repeat { times, interval, turn: step, pattern } => {
+            for i in 0..*times {
+                flatten(pattern, at + seconds(*interval) * i, turn + step * i as f32, bursts);
+            }
+        }
+        Pattern::Group(patterns) => {
+            for pattern in patterns {
+                flatten(pattern, at, turn, bursts);
+            }
+        }
+    }
+}
+
+fn seconds(seconds: f32) -> Duration {
+    Duration::from_secs_f32(seconds.max(0.0))
+}
+
+// Which way each bullet of a Ring, Fan or Aimed goes. angle is the way the emitter faces,
+// target the way to the player if there is one.
+fn directions(burst: &Burst, angle: f32, target: Option<f32>) -> (Vec<f32>, &Shot) {
+    let fan = |count: u32, spread: f32, middle: f32| -> Vec<f32> {
+        let step = if count > 1 { spread / (count as f32 - 1.0) } else { 0.0 };
+        let first = middle - step * (count as f32 - 1.0) / 2.0;
+        (0..count).map(|i| first + step * i as f32).collect()
+    };
+    match &burst.pattern {
+        Pattern::Ring { count, bullet } => {
+            let step = 360.0 / (*count).max(1) as f32;
+            ((0..*count).map(|i| angle + burst.turn + step * i as f32).collect(), bullet)
+        }
+        Pattern::Fan { count, spread, bullet } => (fan(*count, *spread, angle + burst.turn), bullet),
+        Pattern::Aimed { count, spread, bullet } => (fan(*count, *spread, target.unwrap_or(angle) + burst.turn), bullet),
+        _ => unreachable!("only bullets are left after flatten()"),
+    }
+}
+
+// Going heading degrees clockwise from straight up at speed.
+fn velocity(heading: f32, speed: f32) -> Vector2<f32> {
+    let (sin, cos) = heading.to_radians().sin_cos();
+    Vector2 { x: sin, y: cos } * speed
+}
+
+// Degrees clockwise from straight up, the same as bullet angles.
+fn angle_to(from: Vector2<f32>, to: Vector2<f32>) -> f32 {
+    let offset = to - from;
+    offset.x.atan2(offset.y).to_degrees()
+}
+
+// Fires its pattern over and over while the entity it's on is on screen.
+pub struct Emitter {
+    def: Rc<EmitterDef>,
+    timeline: Timeline,
+    // before the first shot, then between one go of the pattern and the next
+    wait: Duration,
+}
+
+impl Emitter {
+    pub fn new(def: Rc<EmitterDef>) -> Self {
+        let timeline = Timeline::new(&def.pattern);
+        let wait = seconds(def.delay);
+        Emitter { def, timeline, wait }
+    }
+
+    // The bursts that came due. Whatever's left of dt after the wait runs the pattern, and
+    // whatever's left after the pattern comes off the cooldown, so the timing doesn't drift.
+    fn advance(&mut self, dt: Duration) -> &[Burst] {
+        if self.wait > dt {
+            self.wait -= dt;
+            return &[];
+        }
+        let dt = dt - self.wait;
+        self.wait = Duration::ZERO;
+        let start = self.timeline.next;
+        self.timeline.advance(dt);
+        let end = self.timeline.next;
+        if self.timeline.is_finished() {
+            self.wait = seconds(self.def.cooldown).saturating_sub(self.timeline.overshoot());
+            self.timeline.restart();
+        }
+        &self.timeline.bursts[start..end]
+    }
+}
+
+struct Bullet {
+    id: u32,
+    // degrees clockwise from straight up
+    heading: f32,
+    speed: f32,
+    acceleration: f32,
+    max_speed: f32,
+    angular_velocity: f32,
+    age: Duration,
+    split: Option<Rc<Split>>,
+}
+
+// A split pattern going off where its bullet was.
+struct Burster {
+    position: Vector2<f32>,
+    heading: f32,
+    timeline: Timeline,
+}
+
+// Resource with every enemy bullet.
+pub struct EnemyBullets {
+    group: EntityGroup,
+    bullets: Vec<Bullet>,
+    bursters: Vec<Burster>,
+    scale: f32,
+    hitbox: f32,
+    next_id: u32,
+    // whatever the bullets can hit, refilled every tick so each bullet only checks what's near it
+    targets: SpatialGrid<EntityId>,
+}
+
+impl EnemyBullets {
+    pub fn new(patterns: &PatternsDef, ctx: &mut Context) -> Result<Self> {
+        let sprite = ctx.assets.sprite(&ctx.gpu, &patterns.sprite)?;
+        let mut group = EntityGroup::new(sprite, &ctx.gpu);
+        group.set_layer(DRAW_LAYER);
+        Ok(EnemyBullets {
+            group,
+            bullets: Vec::new(),
+            bursters: Vec::new(),
+            scale: patterns.scale,
+            hitbox: patterns.hitbox,
+            next_id: 0,
+            targets: SpatialGrid::new(GRID_CELL_SIZE),
+        })
+    }
+
+    pub fn count(&self) -> usize {
+        self.bullets.len()
+    }
+
+    pub fn interpolate(&mut self, alpha: f32) {
+        self.group.interpolate(alpha);
+    }
+
+    fn fire(&mut self, burst: &Burst, position: Vector2<f32>, angle: f32, target: Option<Vector2<f32>>, gpu: &Gpu) -> Result<()> {
+        let (directions, shot) = directions(burst, angle, target.map(|target| angle_to(position, target)));
+        let split = shot.split.as_ref().map(|split| Rc::new((**split).clone()));
+        for heading in directions {
+            let id = self.next_id;
+            self.next_id = self.next_id.wrapping_add(1);
+            self.group.add_instance(id, position, heading, self.scale, &gpu.device)?;
+            self.group.get_instance(id)?.velocity = velocity(heading, shot.speed);
+            self.bullets.push(Bullet {
+                id,
+                heading,
+                speed: shot.speed,
+                acceleration: shot.acceleration,
+                max_speed: shot.max_speed,
+                angular_velocity: shot.angular_velocity,
+                age: Duration::ZERO,
+                split: split.clone(),
+            });
+        }
+        Ok(())
+    }
+
+    fn remove(&mut self, index: usize) -> Result<Bullet> {
+        let bullet = self.bullets.swap_remove(index);
+        self.group.remove_instance(bullet.id)?;
+        Ok(bullet)
+    }
+}
+
+impl Draw for EnemyBullets {
+    fn draw(&self, gpu: &Gpu, batch: &mut SpriteBatch) {
+        self.group.draw(gpu, batch);
+    }
+}
+
+// Where the first player is, for aimed shots.
+fn target(world: &World) -> Option<Vector2<f32>> {
+    let entities = world.borrow::<Entity>();
+    let id = *world.query::<PlayerControl>().with::<Entity>().ids().first()?;
+    entities.get(id).map(|entity| entity.matrix().translation)
+}
+
+fn on_screen(position: Vector2<f32>, ctx: &Context) -> bool {
+    position.x >= 0.0 && position.x <= ctx.width as f32 && position.y >= 0.0 && position.y <= ctx.height as f32
+}
+
+// Runs every emitter that's on screen and every split that's going off.
+pub fn emit(world: &mut World, ctx: &mut Context, dt: Duration) -> Result<()> {
+    let mut bullets = match world.resource_mut::<EnemyBullets>() {
+        Some(bullets) => bullets,
+        None => return Ok(()),
+    };
+    let target = target(world);
+    let entities = world.borrow::<Entity>();
+    let enemies = world.try_borrow::<Enemy>();
+    for (id, emitter) in world.borrow_mut::<Emitter>().iter_mut() {
+        let position = match entities.get(id) {
+            Some(entity) => entity.matrix().translation,
+            None => continue,
+        };
+        let dying = enemies.as_ref().and_then(|enemies| enemies.get(id)).is_some_and(Enemy::is_dying);
+        if dying || !on_screen(position, ctx) {
+            continue;
+        }
+        let angle = emitter.def.angle;
+        for burst in emitter.advance(dt) {
+            bullets.fire(burst, position, angle, target, &ctx.gpu)?;
+        }
+    }
+    let mut bursters = std::mem::take(&mut bullets.bursters);
+    for burster in &mut bursters {
+        for burst in burster.timeline.advance(dt) {
+            bullets.fire(burst, burster.position, burster.heading, target, &ctx.gpu)?;
+        }
+    }
+    bursters.retain(|burster| !burster.timeline.is_finished());
+    bullets.bursters = bursters;
+    Ok(())
+}
+
+// Speeds up and turns every bullet, moves them, splits the ones that are due and drops the
+// ones that left the screen.
+pub fn update_bullets(world: &mut World, ctx: &mut Context, dt: Duration) -> Result<()> {
+    let mut bullets = match world.resource_mut::<EnemyBullets>() {
+        Some(bullets) => bullets,
+        None => return Ok(()),
+    };
+    let bullets = &mut *bullets;
+    let seconds = dt.as_secs_f32();
+    for bullet in &mut bullets.bullets {
+        bullet.age += dt;
+        let speed = (bullet.speed + bullet.acceleration * seconds).min(bullet.max_speed).max(0.0);
+        let heading = bullet.heading + bullet.angular_velocity * seconds;
+        // most bullets just go straight, they only get uploaded again when they're drawn moving
+        if speed == bullet.speed && heading == bullet.heading {
+            continue;
+        }
+        bullet.speed = speed;
+        bullet.heading = heading;
+        let instance = bullets.group.get_instance(bullet.id)?;
+        instance.velocity = velocity(heading, speed);
+        instance.set_rotation(heading);
+    }
+    bullets.group.integrate(dt);
+    // all the way off the screen, not just its middle
+    let margin = bullets.hitbox * bullets.scale * 4.0;
+    let mut index = 0;
+    while index < bullets.bullets.len() {
+        let bullet = &bullets.bullets[index];
+        let position = bullets.group.instance(bullet.id)?.position();
+        let split = bullet.split.as_ref().filter(|split| bullet.age.as_secs_f32() >= split.after).cloned();
+        let outside = position.x < -margin
+            || position.x > ctx.width as f32 + margin
+            || position.y < -margin
+            || position.y > ctx.height as f32 + margin;
+        if split.is_none() && !outside {
+            index += 1;
+            continue;
+        }
+        let bullet = bullets.remove(index)?;
+        if let Some(split) = split {
+            bullets.bursters.push(Burster {
+                position,
+                heading: bullet.heading,
+                timeline: Timeline::new(&split.pattern),
+            });
+        }
+    }
+    Ok(())
+}
+
+// Enemy bullets that touch the player are gone and the game's over.
+pub fn hits(world: &mut World, ctx: &mut Context, _dt: Duration) -> Result<()> {
+    let mut hit = false;
+    {
+        let mut bullets = match world.resource_mut::<EnemyBullets>() {
+            Some(bullets) => bullets,
+            None => return Ok(()),
+        };
+        let bullets = &mut *bullets;
+        let hitbox = Collider::Circle {
+            center: Vector2 { x: 0.0, y: 0.0 },
+            radius: bullets.hitbox,
+        };
+        let colliders = world.borrow::<Collision2D>();
+        bullets.targets.clear();
+        for (id, collider) in colliders.iter() {
+            if collider.layer() & layers::PLAYER != 0 {
+                bullets.targets.insert(id, collider);
+            }
+        }
+        let mut index = 0;
+        while index < bullets.bullets.len() {
+            let id = bullets.bullets[index].id;
+            let mut collision = Collision2D::with_shape(hitbox.clone(), &bullets.group.get_instance(id)?.matrix());
+            collision.set_layers(layers::ENEMY_BULLET, layers::PLAYER);
+            let touching = bullets.targets.query(&collision).into_iter().any(|target| {
+                colliders
+                    .get(target)
+                    .is_some_and(|other| collision.check_collision(other))
+            });
+            if touching {
+                bullets.remove(index)?;
+                hit = true;
+            } else {
+                index += 1;
+            }
+        }
+    }
+    if hit {
+        player_hit(world, ctx);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::canvas::ScalingMode;
+    use crate::engine::headless;
+
+    fn ms(millis: u64) -> Duration {
+        Duration::from_millis(millis)
+    }
+
+    fn ring(count: u32) -> Pattern {
+        Pattern::Ring {
+            count,
+            bullet: Shot::default(),
+        }
+    }
+
+    fn burst(pattern: Pattern, turn: f32) -> Burst {
+        Burst {
+            at: Duration::ZERO,
+            turn,
+            pattern,
+        }
+    }
+
+    fn emitter(pattern: Pattern, delay: f32, cooldown: f32) -> Emitter {
+        Emitter::new(Rc::new(EmitterDef {
+            pattern,
+            delay,
+            cooldown,
+            angle: default_angle(),
+        }))
+    }
+
+    fn assert_angles(angles: &[f32], expected: &[f32]) {
+        assert_eq!(angles.len(), expected.len(), "{:?} != {:?}", angles, expected);
+        for (angle, expected) in angles.iter().zip(expected) {
+            assert!((angle - expected).abs() < 1e-4, "{:?} != {:?}", angles, expected);
+        }
+    }
+
+    #[test]
+    fn rings_go_all_the_way_around_turned_by_the_burst() {
+        let (angles, _) = directions(&burst(ring(4), 10.0), 180.0, None);
+        assert_angles(&angles, &[190.0, 280.0, 370.0, 460.0]);
+        let (angles, _) = directions(&burst(ring(0), 0.0), 180.0, None);
+        assert!(angles.is_empty());
+    }
+
+    #[test]
+    fn fans_spread_around_the_emitters_angle() {
+        let fan = |count| Pattern::Fan {
+            count,
+            spread: 40.0,
+            bullet: Shot::default(),
+        };
+        let (angles, _) = directions(&burst(fan(3), 0.0), 180.0, None);
+        assert_angles(&angles, &[160.0, 180.0, 200.0]);
+        let (angles, _) = directions(&burst(fan(1), 5.0), 180.0, None);
+        assert_angles(&angles, &[185.0]);
+    }
+
+    #[test]
+    fn aimed_fans_spread_around_the_player_or_the_emitter_without_one() {
+        let aimed = Pattern::Aimed {
+            count: 2,
+            spread: 30.0,
+            bullet: Shot::default(),
+        };
+        let target = angle_to(Vector2 { x: 0.0, y: 0.0 }, Vector2 { x: 10.0, y: 0.0 });
+        let (angles, _) = directions(&burst(aimed.clone(), 0.0), 180.0, Some(target));
+        assert_angles(&angles, &[75.0, 105.0]);
+        let (angles, _) = directions(&burst(aimed, 0.0), 180.0, None);
+        assert_angles(&angles, &[165.0, 195.0]);
+    }
+
+    #[test]
+    fn spirals_flatten_into_turning_rings() {
+        let spiral = Pattern::Spiral {
+            arms: 3,
+            times: 4,
+            interval: 0.25,
+            turn: 10.0,
+            bullet: Shot::default(),
+        };
+        let timeline = Timeline::new(&spiral);
+        let times: Vec<_> = timeline.bursts.iter().map(|burst| burst.at).collect();
+        assert_eq!(times, [ms(0), ms(250), ms(500), ms(750)]);
+        let turns: Vec<_> = timeline.bursts.iter().map(|burst| burst.turn).collect();
+        assert_angles(&turns, &[0.0, 10.0, 20.0, 30.0]);
+        assert!(timeline.bursts.iter().all(|burst| matches!(burst.pattern, Pattern::Ring { count: 3, .. })));
+    }
+
+    #[test]
+    fn repeats_of_groups_keep_their_order_and_add_up_turns() {
+        let pattern = Pattern::Repeat {
+            times: 2,
+            interval: 0.5,
+            turn: 15.0,
+            pattern: Box::new(Pattern::Group(vec![
+                Pattern::Repeat {
+                    times: 2,
+                    interval: 0.25,
+                    turn: 1.0,
+                    pattern: Box::new(ring(1)),
+                },
+                ring(2),
+            ])),
+        };
+        let timeline = Timeline::new(&pattern);
+        let bursts: Vec<_> = timeline
+            .bursts
+            .iter()
+            .map(|burst| match burst.pattern {
+                Pattern::Ring { count, .. } => (burst.at, burst.turn, count),
+                _ => unreachable!(),
+            })
+            .collect();
+        assert_eq!(
+            bursts,
+            [
+                (ms(0), 0.0, 1),
+                (ms(0), 0.0, 2),
+                (ms(250), 1.0, 1),
+                (ms(500), 15.0, 1),
+                (ms(500), 15.0, 2),
+                (ms(750), 16.0, 1),
+            ]
+        );
+    }
+
+    #[test]
+    fn emitters_wait_out_the_delay_then_fire_on_schedule() {
+        let pattern = Pattern::Repeat {
+            times: 2,
+            interval: 0.25,
+            turn: 0.0,
+            pattern: Box::new(ring(1)),
+        };
+        let mut emitter = emitter(pattern, 0.5, 1.0);
+        assert!(emitter.advance(ms(375)).is_empty());
+        // 125ms of the tick was the delay, the rest counts towards the pattern
+        assert_eq!(emitter.advance(ms(250)).len(), 1);
+        assert_eq!(emitter.advance(ms(125)).len(), 1);
+        assert_eq!(emitter.wait, ms(1000));
+        assert!(emitter.advance(ms(500)).is_empty());
+        assert_eq!(emitter.advance(ms(750)).len(), 2);
+        assert_eq!(emitter.wait, ms(1000));
+    }
+
+    #[test]
+    fn time_past_the_end_of_a_pattern_comes_off_the_cooldown() {
+        let pattern = Pattern::Repeat {
+            times: 2,
+            interval: 0.25,
+            turn: 0.0,
+            pattern: Box::new(ring(1)),
+        };
+        let mut emitter = emitter(pattern, 0.0, 1.0);
+        assert_eq!(emitter.advance(ms(125)).len(), 1);
+        assert_eq!(emitter.advance(ms(250)).len(), 1);
+        assert_eq!(emitter.wait, ms(875));
+        // a tick longer than the pattern and its cooldown leaves nothing to wait
+        let mut emitter = Emitter::new(Rc::new(EmitterDef { cooldown: 0.5, ..(*emitter.def).clone() }));
+        assert_eq!(emitter.advance(ms(2000)).len(), 2);
+        assert_eq!(emitter.wait, Duration::ZERO);
+    }
+
+    fn split_shot(after: f32) -> Shot {
+        Shot {
+            split: Some(Box::new(Split { after, pattern: ring(3) })),
+            ..Shot::default()
+        }
+    }
+
+    #[test]
+    fn bullets_split_once_theyre_old_enough() {
+        let gpu = match headless::for_tests(4, 4) {
+            Some(headless) => headless.gpu,
+            None => return,
+        };
+        let mut ctx = Context::new(gpu, 64, 64, ScalingMode::Fit);
+        let patterns = PatternsDef {
+            sprite: "assets/bullet.png".to_string(),
+            scale: 1.0,
+            hitbox: 3.0,
+            emitters: BTreeMap::new(),
+        };
+        let mut bullets = EnemyBullets::new(&patterns, &mut ctx).unwrap();
+        let shot = Shot { speed: 0.0, ..split_shot(0.5) };
+        let fan = Pattern::Fan { count: 1, spread: 0.0, bullet: shot };
+        bullets.fire(&burst(fan, 0.0), Vector2 { x: 32.0, y: 32.0 }, 180.0, None, &ctx.gpu).unwrap();
+        let mut world = World::new();
+        world.register::<Entity>();
+        world.register::<Emitter>();
+        world.insert_resource(bullets);
+        let count = |world: &World| world.resource::<EnemyBullets>().unwrap().count();
+
+        update_bullets(&mut world, &mut ctx, ms(250)).unwrap();
+        assert_eq!(count(&world), 1);
+        update_bullets(&mut world, &mut ctx, ms(250)).unwrap();
+        assert_eq!(count(&world), 0);
+        // the split goes off where the bullet was on the next emit
+        emit(&mut world, &mut ctx, ms(0)).unwrap();
+        assert_eq!(count(&world), 3);
+        assert!(world.resource::<EnemyBullets>().unwrap().bursters.is_empty());
+    }
+
+    #[test]
+    fn emitters_with_times_that_arent_durations_dont_load() {
+        let good = |pattern| EmitterDef {
+            pattern,
+            delay: 0.5,
+            cooldown: 1.0,
+            angle: default_angle(),
+        };
+        assert!(check_emitter(&good(ring(8))).is_ok());
+        assert!(check_emitter(&EmitterDef { delay: -1.0, ..good(ring(8)) }).is_err());
+        assert!(check_emitter(&EmitterDef { cooldown: f32::INFINITY, ..good(ring(8)) }).is_err());
+        let nan_split = Pattern::Fan {
+            count: 3,
+            spread: 10.0,
+            bullet: split_shot(f32::NAN),
+        };
+        assert!(check_emitter(&good(nan_split)).is_err());
+        let huge = Pattern::Spiral {
+            arms: 3,
+            times: 2,
+            interval: 1e30,
+            turn: 0.0,
+            bullet: Shot::default(),
+        };
+        assert!(check_emitter(&good(huge)).is_err());
+        let too_long = Pattern::Repeat {
+            times: u32::MAX,
+            interval: 1e10,
+            turn: 0.0,
+            pattern: Box::new(ring(1)),
+        };
+        assert!(check_emitter(&good(too_long)).is_err());
+    }
+
+    #[test]
+    fn the_patterns_file_loads() {
+        let patterns = load(PATTERNS_PATH).unwrap();
+        assert!(!patterns.emitters.is_empty());
+    }
+}
@@ -1,77 +1,138 @@
+use crate::actors::danmaku::{Emitter, Emitters};
+use crate::actors::{layers, GameSprites};
+use crate::engine::assets::SpriteHandle;
 use crate::engine::collision_2d::Collision2D;
+use crate::engine::context::Context;
+use crate::engine::ecs::components::DespawnOutside;
+use crate::engine::ecs::{EntityId, World};
 use crate::engine::entity::Entity;
-use crate::engine::draw::Draw;
-use wgpu::Surface;
-use wgpu::Queue;
-use wgpu::Device;
-use wgpu::Adapter;
-use wgpu::SurfaceConfiguration;
-use std::time::Duration;
+use crate::engine::error::Result;
 use cgmath::Vector2;
+use rand::seq::SliceRandom;
+use rand::Rng;
+use std::time::Duration;
+
+const DRAW_LAYER: i32 = 1;
+const SCALE: f32 = 2.0;
+// how many are on screen at once, a new one comes in as soon as one goes
+const MAX_ENEMIES: usize = 15;
+// how long a shot down enemy takes to fade out
+const DEATH_FADE: Duration = Duration::from_millis(250);
 
 pub struct Enemy {
-    entity: Entity,
-    collision: Collision2D,
-    alive: bool,
-    direction: f32,
-    velocity: f64,
+    // time left fading out, None while it's still flying
+    dying: Option<Duration>,
+    // pixels per second downwards, sideways is a bit slower
+    speed: f32,
 }
 
 impl Enemy {
-    pub fn new(filepath: &str,
-        x: u32,
-        y: u32,
-        velocity: f64,
-        rotation: f32,
-        scale: f32,
-        surface: &Surface,
-        config: &SurfaceConfiguration,
-        adapter: &wgpu::Adapter,
-        queue: &wgpu::Queue,
-        device: &wgpu::Device,) -> Self {
-        let entity = Entity::new(filepath, x, y, rotation, scale, surface, config, adapter, queue, device);
-        let collision = Collision2D::new(entity.position, entity.sprite.diffuse_texture.texture.width() as f32, entity.sprite.diffuse_texture.texture.height() as f32, scale);
-        Enemy {
-            entity,
-            collision,
-            alive: true,
-            direction: -1.0,
-            velocity,
+    // Starts fading out, it's despawned once it's gone.
+    pub fn kill(&mut self, entity: &mut Entity) {
+        if self.dying.is_none() {
+            self.dying = Some(DEATH_FADE);
+            entity.effects.flash = 1.0;
         }
     }
 
-    pub fn update(&mut self, time_elapsed: &Duration, screen_height: f32, screen_width: f32) {
-        if self.entity.position.y <= 0.0 {
-            self.alive = false;
-        }
-        if self.entity.position.x >= screen_width || self.entity.position.x <= 1.0 {
-            self.direction *= -1.0;
-        }
-        let x = self.entity.position.x + ((self.velocity / 1.5) * time_elapsed.as_secs_f64()) as f32 * self.direction;
-        let y = self.entity.position.y - (self.velocity * time_elapsed.as_secs_f64()) as f32;
-        self.entity.update(x as u32, y as u32, self.entity.rotation(), self.entity.scale());
-        self.collision.update(self.entity.position);
+    // Dying enemies can't be hit again and don't hurt the player.
+    pub fn is_dying(&self) -> bool {
+        self.dying.is_some()
     }
+}
 
-    pub fn get_collision(&self) -> &Collision2D {
-        &self.collision
-    }
+// Flies down the screen bouncing off the sides, gone once it's entirely off the bottom.
+pub fn spawn(world: &mut World,
+    sprite: SpriteHandle,
+    position: Vector2<f32>,
+    speed: f32,
+    rotation: f32,
+    scale: f32) -> EntityId {
+    let mut entity = Entity::new(sprite, position, rotation, scale);
+    entity.velocity = Vector2 {
+        x: -speed / 1.5,
+        y: -speed,
+    };
+    entity.set_layer(DRAW_LAYER);
+    let mut collision = Collision2D::new(entity.size(), &entity.matrix());
+    collision.set_layers(layers::ENEMY, layers::PLAYER | layers::PLAYER_BULLET);
+    let bottom = -entity.size().y * entity.scale().y;
+    let id = world.spawn();
+    world.insert(id, entity);
+    world.insert(id, collision);
+    world.insert(id, Enemy { dying: None, speed });
+    world.insert(id, DespawnOutside {
+        min: Vector2 { x: f32::NEG_INFINITY, y: bottom },
+        max: Vector2 { x: f32::INFINITY, y: f32::INFINITY },
+    });
+    id
+}
 
-    pub fn kill(&mut self) {
-        self.alive = false;
+// Starts it fading out and takes it off the collision layers, so it can't be hit again
+// and doesn't hurt the player.
+pub fn kill(world: &mut World, id: EntityId) {
+    if let (Some(enemy), Some(entity)) = (world.borrow_mut::<Enemy>().get_mut(id), world.borrow_mut::<Entity>().get_mut(id)) {
+        enemy.kill(entity);
     }
-
-    pub fn alive(&self) -> bool {
-        self.alive
+    if let Some(collision) = world.borrow_mut::<Collision2D>().get_mut(id) {
+        collision.set_layers(0, 0);
     }
 }
 
-
-impl Draw for Enemy {
-    fn draw(&self,
-        device: &wgpu::Device,
-        encoder: &mut wgpu::CommandEncoder,
-        view: &wgpu::TextureView) -> Result<(), wgpu::SurfaceError> {
-            self.entity.render(device, encoder, view)
+// Bouncing and fading out, runs before movement.
+pub fn update(world: &mut World, ctx: &mut Context, dt: Duration) -> Result<()> {
+    let screen_width = ctx.width as f32;
+    let mut entities = world.borrow_mut::<Entity>();
+    for (id, enemy) in world.borrow_mut::<Enemy>().iter_mut() {
+        let entity = match entities.get_mut(id) {
+            Some(entity) => entity,
+            None => continue,
+        };
+        if let Some(left) = enemy.dying {
+            // flashes white on the hit, then fades out
+            let left = left.saturating_sub(dt);
+            let fade = left.as_secs_f32() / DEATH_FADE.as_secs_f32();
+            entity.effects.flash = fade;
+            entity.effects.set_opacity(fade);
+            enemy.dying = Some(left);
+            if left.is_zero() {
+                world.queue_despawn(id);
+            }
         }
+        // bounce off the sides
+        if entity.position.x <= 0.0 {
+            entity.velocity.x = enemy.speed / 1.5;
+        } else if entity.position.x >= screen_width {
+            entity.velocity.x = -enemy.speed / 1.5;
+        }
+    }
+    Ok(())
+}
+
+// Tops the enemies back up, new ones come in somewhere above the screen with one of the
+// emitters to shoot with.
+pub fn spawn_wave(world: &mut World, ctx: &mut Context, _dt: Duration) -> Result<()> {
+    if world.borrow::<Enemy>().len() >= MAX_ENEMIES {
+        return Ok(());
+    }
+    let sprite = match world.resource::<GameSprites>() {
+        Some(sprites) => sprites.enemy.clone(),
+        None => return Ok(()),
+    };
+    let mut rng = rand::thread_rng();
+    let width = ctx.width as f32;
+    let height = ctx.height as f32;
+    let position = Vector2 {
+        x: rng.gen_range(0.0..width),
+        y: rng.gen_range(height..height * 2.0),
+    };
+    let speed: f32 = rng.gen_range(300.0..700.0);
+    let emitter = world
+        .resource::<Emitters>()
+        .and_then(|emitters| emitters.0.choose(&mut rng).cloned());
+    let id = spawn(world, sprite, position, speed, 0.0, SCALE);
+    if let Some(emitter) = emitter {
+        world.insert(id, Emitter::new(emitter));
+    }
+    Ok(())
 }
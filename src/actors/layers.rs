@@ -0,0 +1,7 @@
+// Collision layer bits, each collider is on one of them and has a mask of the ones it
+// collides with, see Collision2D::set_layers().
+pub const PLAYER: u32 = 1 << 0;
+pub const PLAYER_BULLET: u32 = 1 << 1;
+pub const ENEMY: u32 = 1 << 2;
+pub const ENEMY_BULLET: u32 = 1 << 3;
+pub const PICKUP: u32 = 1 << 4;
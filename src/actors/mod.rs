@@ -1,3 +1,54 @@
 pub mod player;
 pub mod enemy;
 pub mod bullet;
+pub mod layers;
+pub mod weapon;
+pub mod danmaku;
+
+use crate::actors::weapon::Weapon;
+use crate::engine::assets::SpriteHandle;
+use crate::engine::context::Context;
+use crate::engine::ecs::collision::{CollisionKind, CollisionPass};
+use crate::engine::ecs::World;
+use std::time::Duration;
+
+// Resource with the sprites the spawning systems need.
+pub struct GameSprites {
+    pub player: SpriteHandle,
+    pub enemy: SpriteHandle,
+}
+
+// Resource inserted by player_hit() when an enemy or its bullets reach the player, the
+// scene takes it out.
+pub struct PlayerHit;
+
+// Whatever got the player, it looks and ends the same.
+pub fn player_hit(world: &mut World, ctx: &mut Context) {
+    ctx.camera.shake(12.0, Duration::from_millis(400));
+    world.insert_resource(PlayerHit);
+}
+
+// About the size of an enemy on screen.
+const GRID_CELL_SIZE: f32 = 64.0;
+
+// Bullets shoot down enemies, enemies that reach the player end the game.
+pub fn collisions() -> CollisionPass {
+    let mut pass = CollisionPass::new(GRID_CELL_SIZE);
+    pass.subscribe(layers::PLAYER_BULLET, layers::ENEMY, |world, _ctx, event| {
+        if event.kind == CollisionKind::Enter {
+            world.despawn(event.first);
+            enemy::kill(world, event.second);
+            for (_, weapon) in world.borrow_mut::<Weapon>().iter_mut() {
+                weapon.add_power(1);
+            }
+        }
+        Ok(())
+    });
+    pass.subscribe(layers::PLAYER, layers::ENEMY, |world, ctx, event| {
+        if event.kind == CollisionKind::Enter {
+            player_hit(world, ctx);
+        }
+        Ok(())
+    });
+    pass
+}
@@ -1,89 +1,133 @@
-use crate::engine::collision_2d::Collision2D;
+use crate::actors::layers;
+use crate::controls;
+use crate::engine::assets::SpriteHandle;
+use crate::engine::collision_2d::{Collider, Collision2D};
+use crate::engine::context::Context;
+use crate::engine::ecs::components::{ClampToBounds, Parent};
+use crate::engine::ecs::{EntityId, World};
 use crate::engine::entity::Entity;
-use crate::engine::draw::Draw;
-use wgpu::Surface;
-use wgpu::Queue;
-use wgpu::Device;
-use wgpu::Adapter;
-use wgpu::SurfaceConfiguration;
+use crate::engine::error::Result;
+use cgmath::prelude::*;
+use cgmath::Vector2;
 use std::time::Duration;
 
-pub struct Player {
-    entity: Entity,
-    pub up: bool,
-    pub down: bool,
-    pub left: bool,
-    pub right: bool,
+// The player is always drawn on top.
+const DRAW_LAYER: i32 = 2;
+// pixels per second
+const SPEED: f32 = 1000.0;
+// Only the middle of the ship can be hit, in sprite pixels before scaling.
+const HITBOX_RADIUS: f32 = 3.0;
+// The option drones circle the ship, distance and size are relative to the ship.
+const DRONE_COUNT: usize = 2;
+const DRONE_ORBIT_RADIUS: f32 = 14.0;
+const DRONE_SCALE: f32 = 0.4;
+// degrees per second
+const DRONE_ORBIT_SPEED: f32 = 180.0;
+
+// Which way the player is steering and whether it's firing, set from the input each tick by read_input().
+#[derive(Clone, Copy, Debug)]
+pub struct PlayerControl {
+    // up and right are positive, as long as 1 for full speed
+    pub movement: Vector2<f32>,
+    pub fire: bool,
 }
 
-impl Player {
-    pub fn new(filepath: &str,
-        x: u32,
-        y: u32,
-        rotation: f32,
-        scale: f32,
-        surface: &Surface,
-        config: &SurfaceConfiguration,
-        adapter: &wgpu::Adapter,
-        queue: &wgpu::Queue,
-        device: &wgpu::Device,) -> Self {
-        let entity = Entity::new(filepath, x, y, rotation, scale, surface, config, adapter, queue, device);
-        Player {
-            entity,
-            up: false,
-            down: false,
-            left: false,
-            right: false,
+impl Default for PlayerControl {
+    fn default() -> Self {
+        PlayerControl {
+            movement: Vector2::zero(),
+            fire: false,
         }
     }
-    pub fn update(&mut self, time_elapsed: &Duration, screen_width: u32, screen_height: u32) {
-        let elapsed = time_elapsed.as_secs_f64();
-        let mut x = self.entity.position_x() as i32;
-        let mut y = self.entity.position_y() as i32;
-        let screen_height = screen_height as i32;
-        let screen_width = screen_width as i32;
-        println!("elapsed: {}", elapsed);
-        if self.left {
-            x = self.entity.position_x() as i32 - (1000.0 * elapsed) as i32;
-            if x <= 0 {
-                x = 0;
-            }
-        }
-        if self.right {
-            x = self.entity.position_x() as i32 + (1000.0 * elapsed) as i32;
-            if x >= screen_width {
-                x = screen_width;
-            }
-        }
-        if self.up {
-            y = self.entity.position_y() as i32 + (1000.0 * elapsed) as i32;
-            if y >= screen_height {
-                y = screen_height;
-            }
-        }
-        if self.down {
-            y = self.entity.position_y() as i32 - (1000.0 * elapsed) as i32;
-            if y <= 0 {
-                y = 0;
-            }
+}
+
+// Circles the ship it's parented to.
+pub struct Drone {
+    // degrees
+    angle: f32,
+}
+
+// The ship with its drones as children, so they follow it around. It can't leave the
+// given area.
+pub fn spawn(world: &mut World,
+    sprite: SpriteHandle,
+    drone_sprite: SpriteHandle,
+    position: Vector2<f32>,
+    rotation: f32,
+    scale: f32,
+    bounds: Vector2<f32>) -> EntityId {
+    let mut entity = Entity::new(sprite, position, rotation, scale);
+    entity.set_layer(DRAW_LAYER);
+    let hitbox = Collider::Circle {
+        center: Vector2 { x: 0.0, y: 0.0 },
+        radius: HITBOX_RADIUS,
+    };
+    let mut collision = Collision2D::with_shape(hitbox, &entity.matrix());
+    collision.set_layers(layers::PLAYER, layers::ENEMY | layers::ENEMY_BULLET | layers::PICKUP);
+    let ship = world.spawn();
+    world.insert(ship, entity);
+    world.insert(ship, collision);
+    world.insert(ship, PlayerControl::default());
+    world.insert(ship, ClampToBounds {
+        min: Vector2 { x: 0.0, y: 0.0 },
+        max: bounds,
+    });
+    // spread evenly around the ship
+    let step = 360.0 / DRONE_COUNT as f32;
+    for i in 0..DRONE_COUNT {
+        let drone = Drone { angle: step * i as f32 };
+        let mut entity = Entity::new(drone_sprite.clone(), drone.offset(), 0.0, DRONE_SCALE);
+        entity.set_layer(DRAW_LAYER);
+        let id = world.spawn();
+        world.insert(id, entity);
+        world.insert(id, drone);
+        world.insert(id, Parent(ship));
+    }
+    ship
+}
+
+impl Drone {
+    fn offset(&self) -> Vector2<f32> {
+        let angle = self.angle.to_radians();
+        Vector2 {
+            x: angle.cos() * DRONE_ORBIT_RADIUS,
+            y: angle.sin() * DRONE_ORBIT_RADIUS,
         }
-        self.entity.update(x as u32, y as u32, self.entity.rotation(), self.entity.scale());
     }
+}
 
-    pub fn x(&self) -> u32 {
-        self.entity.position_x()
+pub fn read_input(world: &mut World, ctx: &mut Context, _dt: Duration) -> Result<()> {
+    for (_, control) in world.borrow_mut::<PlayerControl>().iter_mut() {
+        control.movement = ctx.input.direction(controls::LEFT, controls::RIGHT, controls::DOWN, controls::UP);
+        control.fire = ctx.input.held(controls::FIRE);
     }
+    Ok(())
+}
 
-    pub fn y(&self) -> u32 {
-        self.entity.position_y()
+// Turns the movement into velocity, runs before movement. Diagonals are no faster than
+// straight lines.
+pub fn steer(world: &mut World, _ctx: &mut Context, _dt: Duration) -> Result<()> {
+    let mut entities = world.borrow_mut::<Entity>();
+    for (id, control) in world.borrow::<PlayerControl>().iter() {
+        let mut direction = control.movement;
+        if direction.magnitude2() > 1.0 {
+            direction = direction.normalize();
+        }
+        if let Some(entity) = entities.get_mut(id) {
+            entity.velocity = direction * SPEED;
+        }
     }
+    Ok(())
 }
 
-impl Draw for Player {
-    fn draw(&self,
-        device: &wgpu::Device,
-        encoder: &mut wgpu::CommandEncoder,
-        view: &wgpu::TextureView) -> Result<(), wgpu::SurfaceError> {
-            self.entity.render(device, encoder, view)
+// Runs after movement, moving the drones there would lose their previous position.
+pub fn orbit_drones(world: &mut World, _ctx: &mut Context, dt: Duration) -> Result<()> {
+    let mut entities = world.borrow_mut::<Entity>();
+    for (id, drone) in world.borrow_mut::<Drone>().iter_mut() {
+        drone.angle = (drone.angle + DRONE_ORBIT_SPEED * dt.as_secs_f32()) % 360.0;
+        if let Some(entity) = entities.get_mut(id) {
+            entity.update(drone.offset(), 0.0, DRONE_SCALE);
         }
+    }
+    Ok(())
 }
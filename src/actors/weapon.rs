@@ -0,0 +1,333 @@
+use crate::actors::bullet;
+use crate::actors::player::PlayerControl;
+use crate::engine::assets::{AssetManager, SpriteHandle};
+use crate::engine::config;
+use crate::engine::context::Context;
+use crate::engine::ecs::World;
+use crate::engine::entity::Entity;
+use crate::engine::error::Result;
+use crate::engine::gpu::Gpu;
+use cgmath::Vector2;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::rc::Rc;
+use std::time::Duration;
+
+pub const WEAPONS_PATH: &str = "assets/weapons.ron";
+
+// A gun as designed in the weapons file, see assets/weapons.ron.
+#[derive(Clone, Debug, Deserialize)]
+pub struct WeaponDef {
+    pub sprite: String,
+    #[serde(default = "default_scale")]
+    pub scale: f32,
+    // from weakest to strongest
+    pub levels: Vec<WeaponLevel>,
+}
+
+fn default_scale() -> f32 {
+    1.0
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct WeaponLevel {
+    // power needed to reach this level, see Weapon::add_power()
+    #[serde(default)]
+    pub power: u32,
+    // seconds between shots while fire is held
+    pub cooldown: f32,
+    // all fired at once
+    pub volleys: Vec<Volley>,
+}
+
+// A group of bullets fanned out over spread degrees and side by side spacing pixels apart.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct Volley {
+    pub count: u32,
+    // degrees clockwise from straight up, the middle of the fan
+    pub angle: f32,
+    // degrees between the outermost bullets
+    pub spread: f32,
+    // pixels between neighbouring bullets
+    pub spacing: f32,
+    // pixels per second
+    pub speed: f32,
+    // from the middle of the ship
+    pub offset: (f32, f32),
+}
+
+impl Default for Volley {
+    fn default() -> Self {
+        Volley {
+            count: 1,
+            angle: 0.0,
+            spread: 0.0,
+            spacing: 0.0,
+            speed: 2400.0,
+            offset: (0.0, 0.0),
+        }
+    }
+}
+
+impl Volley {
+    // Where each bullet starts relative to the ship and which way it goes.
+    fn shots(&self) -> impl Iterator<Item = (Vector2<f32>, f32)> + '_ {
+        let middle = (self.count as f32 - 1.0) / 2.0;
+        let step = if self.count > 1 {
+            self.spread / (self.count as f32 - 1.0)
+        } else {
+            0.0
+        };
+        (0..self.count).map(move |i| {
+            let from_middle = i as f32 - middle;
+            let offset = Vector2 {
+                x: self.offset.0 + from_middle * self.spacing,
+                y: self.offset.1,
+            };
+            (offset, self.angle + from_middle * step)
+        })
+    }
+}
+
+// Every gun in the weapons file by name.
+pub fn load(path: &str) -> Result<BTreeMap<String, WeaponDef>> {
+    let weapons: BTreeMap<String, WeaponDef> = config::load_ron(path)?;
+    if let Some((name, _)) = weapons.iter().find(|(_, weapon)| weapon.levels.is_empty()) {
+        return Err(config::config_error(path, format!("weapon {} has no levels", name)));
+    }
+    Ok(weapons)
+}
+
+// The gun a ship has equipped, fires while the ship's PlayerControl has fire held.
+pub struct Weapon {
+    def: Rc<WeaponDef>,
+    sprite: SpriteHandle,
+    level: usize,
+    power: u32,
+    // seconds until it can fire again, goes below 0 by however much the last tick overshot
+    // so the rate doesn't get rounded up to whole ticks
+    cooldown: f32,
+}
+
+impl Weapon {
+    pub fn new(def: Rc<WeaponDef>, assets: &mut AssetManager, gpu: &Gpu) -> Result<Self> {
+        let sprite = assets.sprite(gpu, &def.sprite)?;
+        Ok(Weapon {
+            def,
+            sprite,
+            level: 0,
+            power: 0,
+            cooldown: 0.0,
+        })
+    }
+
+    pub fn level(&self) -> usize {
+        self.level
+    }
+
+    // Clamped to the levels the weapon has.
+    pub fn set_level(&mut self, level: usize) {
+        self.level = level.min(self.def.levels.len() - 1);
+    }
+
+    pub fn power(&self) -> u32 {
+        self.power
+    }
+
+    // Seconds ago each volley due this tick should have gone, oldest first. Guns faster than
+    // the tick rate are due more than once a tick.
+    fn trigger(&mut self, dt: Duration, firing: bool) -> Vec<f32> {
+        if !firing {
+            self.cooldown = (self.cooldown - dt.as_secs_f32()).max(0.0);
+            return Vec::new();
+        }
+        // a gun that was already waiting fires right away, it isn't late
+        self.cooldown = if self.cooldown > 0.0 { self.cooldown - dt.as_secs_f32() } else { 0.0 };
+        let cooldown = self.def.levels[self.level].cooldown;
+        let mut due = Vec::new();
+        while self.cooldown <= 0.0 {
+            due.push(-self.cooldown);
+            if cooldown <= 0.0 {
+                self.cooldown = 0.0;
+                break;
+            }
+            self.cooldown += cooldown;
+        }
+        due
+    }
+
+    // Goes up a level whenever there's enough power for the next one.
+    pub fn add_power(&mut self, power: u32) {
+        self.power = self.power.saturating_add(power);
+        while self.def.levels.get(self.level + 1).is_some_and(|next| self.power >= next.power) {
+            self.level += 1;
+        }
+    }
+}
+
+// Fires every ship's weapon that's held down and has cooled down.
+pub fn fire(world: &mut World, ctx: &mut Context, dt: Duration) -> Result<()> {
+    let mut shots = Vec::new();
+    {
+        let entities = world.borrow::<Entity>();
+        let controls = world.borrow::<PlayerControl>();
+        for (id, weapon) in world.borrow_mut::<Weapon>().iter_mut() {
+            let firing = controls.get(id).is_some_and(|control| control.fire) && entities.contains(id);
+            let due = weapon.trigger(dt, firing);
+            let entity = match entities.get(id) {
+                Some(entity) if !due.is_empty() => entity,
+                _ => continue,
+            };
+            let position = entity.matrix().translation;
+            let def = &weapon.def;
+            for late in due {
+                for volley in &def.levels[weapon.level].volleys {
+                    for (offset, angle) in volley.shots() {
+                        // volleys that should have gone earlier in the tick are already on their way
+                        let (sin, cos) = angle.to_radians().sin_cos();
+                        let travelled = Vector2 { x: sin, y: cos } * volley.speed * late;
+                        shots.push((weapon.sprite.clone(), position + offset + travelled, angle, volley.speed, def.scale));
+                    }
+                }
+            }
+        }
+    }
+    let screen = Vector2 {
+        x: ctx.width as f32,
+        y: ctx.height as f32,
+    };
+    for (sprite, position, angle, speed, scale) in shots {
+        bullet::spawn(world, sprite, position, angle, speed, scale, screen);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::headless;
+
+    const TICK: Duration = Duration::from_nanos(1_000_000_000 / 60);
+
+    fn level(power: u32, cooldown: f32) -> WeaponLevel {
+        WeaponLevel {
+            power,
+            cooldown,
+            volleys: vec![Volley::default()],
+        }
+    }
+
+    // None without a gpu for the bullet sprite, the test is skipped.
+    fn weapon(levels: Vec<WeaponLevel>) -> Option<Weapon> {
+        let gpu = headless::for_tests(4, 4)?.gpu;
+        let def = WeaponDef {
+            sprite: "assets/bullet.png".to_string(),
+            scale: 1.0,
+            levels,
+        };
+        Some(Weapon::new(Rc::new(def), &mut AssetManager::new(), &gpu).unwrap())
+    }
+
+    fn assert_close(a: f32, b: f32) {
+        assert!((a - b).abs() < 1e-4, "{} != {}", a, b);
+    }
+
+    #[test]
+    fn volleys_fan_out_around_their_angle() {
+        let volley = Volley {
+            count: 3,
+            angle: 5.0,
+            spread: 20.0,
+            spacing: 10.0,
+            offset: (2.0, 8.0),
+            ..Volley::default()
+        };
+        let shots: Vec<_> = volley.shots().collect();
+        assert_eq!(shots.len(), 3);
+        for ((offset, angle), (x, expected)) in shots.into_iter().zip([(-8.0, -5.0), (2.0, 5.0), (12.0, 15.0)]) {
+            assert_close(offset.x, x);
+            assert_close(offset.y, 8.0);
+            assert_close(angle, expected);
+        }
+    }
+
+    #[test]
+    fn single_shots_go_straight_down_the_middle() {
+        let volley = Volley {
+            angle: -10.0,
+            spread: 45.0,
+            spacing: 30.0,
+            ..Volley::default()
+        };
+        let shots: Vec<_> = volley.shots().collect();
+        assert_eq!(shots.len(), 1);
+        assert_eq!(shots[0], (Vector2 { x: 0.0, y: 0.0 }, -10.0));
+        let empty = Volley {
+            count: 0,
+            ..Volley::default()
+        };
+        assert_eq!(empty.shots().count(), 0);
+    }
+
+    #[test]
+    fn power_levels_the_weapon_up() {
+        let mut weapon = match weapon(vec![level(0, 0.1), level(10, 0.1), level(25, 0.1), level(50, 0.1)]) {
+            Some(weapon) => weapon,
+            None => return,
+        };
+        weapon.add_power(9);
+        assert_eq!(weapon.level(), 0);
+        weapon.add_power(1);
+        assert_eq!(weapon.level(), 1);
+        // more than one level at once
+        weapon.add_power(40);
+        assert_eq!((weapon.power(), weapon.level()), (50, 3));
+        // past the last level the power still counts
+        weapon.add_power(u32::MAX);
+        assert_eq!((weapon.power(), weapon.level()), (u32::MAX, 3));
+        weapon.set_level(99);
+        assert_eq!(weapon.level(), 3);
+    }
+
+    #[test]
+    fn cooldown_leftovers_carry_over() {
+        let mut weapon = match weapon(vec![level(0, 0.12)]) {
+            Some(weapon) => weapon,
+            None => return,
+        };
+        // 0.12s isn't a whole number of ticks, rounding up to 8 would only give 75 volleys
+        let volleys: usize = (0..600).map(|_| weapon.trigger(TICK, true).len()).sum();
+        assert!((83..=85).contains(&volleys), "{} volleys in 10s", volleys);
+    }
+
+    #[test]
+    fn fast_guns_fire_several_volleys_a_tick_spaced_out_in_time() {
+        let mut weapon = match weapon(vec![level(0, 0.005)]) {
+            Some(weapon) => weapon,
+            None => return,
+        };
+        assert_eq!(weapon.trigger(TICK, true), [0.0]);
+        let due = weapon.trigger(TICK, true);
+        assert_eq!(due.len(), 3);
+        let tick = TICK.as_secs_f32();
+        assert_close(due[0], tick - 0.005);
+        assert_close(due[0] - due[1], 0.005);
+        assert_close(due[1] - due[2], 0.005);
+    }
+
+    #[test]
+    fn letting_go_doesnt_save_up_shots() {
+        let mut weapon = match weapon(vec![level(0, 0.1)]) {
+            Some(weapon) => weapon,
+            None => return,
+        };
+        assert_eq!(weapon.trigger(TICK, true).len(), 1);
+        for _ in 0..120 {
+            assert!(weapon.trigger(TICK, false).is_empty());
+        }
+        // fires again right away, once
+        assert_eq!(weapon.trigger(TICK, true), [0.0]);
+        assert!(weapon.trigger(TICK, true).is_empty());
+    }
+}
@@ -0,0 +1,69 @@
+use crate::engine::gamepad::{GamepadAxis, GamepadButton};
+use crate::engine::input::{Binding, Direction, InputMap};
+use winit::event::VirtualKeyCode;
+
+// The game's actions, see InputMap.
+pub const UP: &str = "up";
+pub const DOWN: &str = "down";
+pub const LEFT: &str = "left";
+pub const RIGHT: &str = "right";
+pub const FIRE: &str = "fire";
+pub const BOMB: &str = "bomb";
+pub const PAUSE: &str = "pause";
+pub const CONFIRM: &str = "confirm";
+pub const QUIT: &str = "quit";
+
+// Edit it to change the bindings, assets/controls_arcade.ron has the usual arcade stick
+// layout to copy over it.
+pub const CONTROLS_PATH: &str = "assets/controls.ron";
+
+// Used when the controls file can't be read, e.g. on the web. Arrows, WASD and a gamepad's
+// left stick or d-pad all work.
+pub fn default_map() -> InputMap {
+    use GamepadAxis::*;
+    use GamepadButton::*;
+    use VirtualKeyCode::*;
+    let mut map = InputMap::new();
+    let defaults: [(&str, &[VirtualKeyCode]); 9] = [
+        (UP, &[Up, W]),
+        (DOWN, &[Down, S]),
+        (LEFT, &[Left, A]),
+        (RIGHT, &[Right, D]),
+        (FIRE, &[Z, J]),
+        (BOMB, &[X, K]),
+        (PAUSE, &[P]),
+        (CONFIRM, &[Return, Space]),
+        (QUIT, &[Escape]),
+    ];
+    for (action, keys) in defaults {
+        map.rebind(action, keys.iter().map(|&key| Binding::Key(key)).collect());
+    }
+    let pad: [(&str, &[Binding]); 9] = [
+        (UP, &[Binding::Button(DPadUp), Binding::Axis(LeftStickY, Direction::Positive)]),
+        (DOWN, &[Binding::Button(DPadDown), Binding::Axis(LeftStickY, Direction::Negative)]),
+        (LEFT, &[Binding::Button(DPadLeft), Binding::Axis(LeftStickX, Direction::Negative)]),
+        (RIGHT, &[Binding::Button(DPadRight), Binding::Axis(LeftStickX, Direction::Positive)]),
+        (FIRE, &[Binding::Button(South)]),
+        (BOMB, &[Binding::Button(East)]),
+        (PAUSE, &[Binding::Button(Start)]),
+        (CONFIRM, &[Binding::Button(South), Binding::Button(Start)]),
+        (QUIT, &[]),
+    ];
+    for (action, bindings) in pad {
+        for &binding in bindings {
+            map.bind(action, binding);
+        }
+    }
+    map
+}
+
+// The bindings from the controls file, or the defaults if there isn't a usable one.
+pub fn load() -> InputMap {
+    match InputMap::load(CONTROLS_PATH) {
+        Ok(map) => map,
+        Err(err) => {
+            log::warn!("using the default controls: {}", err);
+            default_map()
+        }
+    }
+}
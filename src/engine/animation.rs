@@ -0,0 +1,339 @@
+use crate::engine::sprite::Sprite;
+use cgmath::Vector2;
+use std::collections::HashMap;
+use std::rc::Rc;
+use std::time::Duration;
+
+// One frame of a sheet, uv_rect is in texture coordinates like Sprite::uv_rect.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Frame {
+    pub uv_rect: [f32; 4],
+    pub size: Vector2<f32>,
+}
+
+// The frames a sprite is cut into. Frame rects are in pixels relative to the sprite, so a
+// sheet works the same whether the sprite is a loose file or a region of an atlas.
+// Any entity drawn with that sprite can be animated with the sheet.
+pub struct SpriteSheet {
+    frames: Vec<Frame>,
+    names: HashMap<String, usize>,
+}
+
+impl SpriteSheet {
+    // Cuts the sprite into an even grid, frames are numbered left to right, top to bottom.
+    // Panics if there are no columns or rows.
+    pub fn grid(sprite: &Sprite, columns: u32, rows: u32) -> Self {
+        assert!(columns > 0 && rows > 0, "a sprite sheet grid needs at least one column and row, got {}x{}", columns, rows);
+        let width = sprite.size.x as u32 / columns;
+        let height = sprite.size.y as u32 / rows;
+        let mut sheet = SpriteSheet {
+            frames: Vec::new(),
+            names: HashMap::new(),
+        };
+        for row in 0..rows {
+            for column in 0..columns {
+                sheet.add_frame(sprite, column * width, row * height, width, height);
+            }
+        }
+        sheet
+    }
+
+    // Named frames at arbitrary rects, for sheets that aren't a regular grid.
+    pub fn from_frames(sprite: &Sprite, frames: &[(&str, u32, u32, u32, u32)]) -> Self {
+        let mut sheet = SpriteSheet {
+            frames: Vec::new(),
+            names: HashMap::new(),
+        };
+        for &(name, x, y, width, height) in frames {
+            let index = sheet.add_frame(sprite, x, y, width, height);
+            sheet.names.insert(name.to_string(), index);
+        }
+        sheet
+    }
+
+    fn add_frame(&mut self, sprite: &Sprite, x: u32, y: u32, width: u32, height: u32) -> usize {
+        // pixels of the sprite to texture coordinates
+        let u_per_pixel = sprite.uv_rect[2] / sprite.size.x;
+        let v_per_pixel = sprite.uv_rect[3] / sprite.size.y;
+        self.frames.push(Frame {
+            uv_rect: [
+                sprite.uv_rect[0] + x as f32 * u_per_pixel,
+                sprite.uv_rect[1] + y as f32 * v_per_pixel,
+                width as f32 * u_per_pixel,
+                height as f32 * v_per_pixel,
+            ],
+            size: Vector2 {
+                x: width as f32,
+                y: height as f32,
+            },
+        });
+        self.frames.len() - 1
+    }
+
+    pub fn frame(&self, index: usize) -> Option<&Frame> {
+        self.frames.get(index)
+    }
+
+    pub fn frame_index(&self, name: &str) -> Option<usize> {
+        self.names.get(name).copied()
+    }
+
+    pub fn frame_count(&self) -> usize {
+        self.frames.len()
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PlayMode {
+    Loop,
+    // forwards then backwards again, without showing the end frames twice
+    PingPong,
+    // stops on the last frame
+    Once,
+}
+
+// A sequence of sheet frames, each shown for its own duration.
+pub struct AnimationClip {
+    frames: Vec<(usize, Duration)>,
+    mode: PlayMode,
+}
+
+impl AnimationClip {
+    // Panics if frames is empty.
+    pub fn new(frames: &[(usize, Duration)], mode: PlayMode) -> Self {
+        assert!(!frames.is_empty(), "an animation clip needs at least one frame");
+        AnimationClip {
+            frames: frames.to_vec(),
+            mode,
+        }
+    }
+
+    // Every frame shown for the same time.
+    pub fn uniform(frames: &[usize], frame_duration: Duration, mode: PlayMode) -> Self {
+        let frames: Vec<(usize, Duration)> = frames.iter().map(|&frame| (frame, frame_duration)).collect();
+        AnimationClip::new(&frames, mode)
+    }
+
+    // Frames by their name in the sheet, None if one of them isn't there.
+    pub fn from_names(sheet: &SpriteSheet, frames: &[(&str, Duration)], mode: PlayMode) -> Option<Self> {
+        let frames = frames
+            .iter()
+            .map(|&(name, duration)| sheet.frame_index(name).map(|index| (index, duration)))
+            .collect::<Option<Vec<_>>>()?;
+        Some(AnimationClip::new(&frames, mode))
+    }
+
+    pub fn mode(&self) -> PlayMode {
+        self.mode
+    }
+
+    pub fn len(&self) -> usize {
+        self.frames.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.frames.is_empty()
+    }
+}
+
+// Plays clips from a sheet, carried by an Entity or an EntityGroup instance which then draw
+// the current frame instead of the whole sprite.
+#[derive(Clone)]
+pub struct Animator {
+    sheet: Rc<SpriteSheet>,
+    clip: Rc<AnimationClip>,
+    // position in the clip, not the sheet
+    index: usize,
+    elapsed: Duration,
+    forward: bool,
+    finished: bool,
+}
+
+impl Animator {
+    pub fn new(sheet: Rc<SpriteSheet>, clip: Rc<AnimationClip>) -> Self {
+        Animator {
+            sheet,
+            clip,
+            index: 0,
+            elapsed: Duration::ZERO,
+            forward: true,
+            finished: false,
+        }
+    }
+
+    // Starts the given clip from its first frame, does nothing if it's already playing.
+    pub fn play(&mut self, clip: Rc<AnimationClip>) {
+        if Rc::ptr_eq(&self.clip, &clip) {
+            return;
+        }
+        self.clip = clip;
+        self.restart();
+    }
+
+    pub fn restart(&mut self) {
+        self.index = 0;
+        self.elapsed = Duration::ZERO;
+        self.forward = true;
+        self.finished = false;
+    }
+
+    // Returns whether the frame shown changed.
+    pub fn update(&mut self, dt: Duration) -> bool {
+        let start = self.index;
+        self.elapsed += dt;
+        while !self.finished {
+            let duration = self.clip.frames[self.index].1;
+            if self.elapsed < duration {
+                break;
+            }
+            self.elapsed -= duration;
+            self.advance();
+            // a zero length frame would otherwise spin here forever
+            if duration.is_zero() {
+                break;
+            }
+        }
+        self.index != start
+    }
+
+    fn advance(&mut self) {
+        let last = self.clip.frames.len() - 1;
+        match self.clip.mode {
+            PlayMode::Loop => self.index = if self.index == last { 0 } else { self.index + 1 },
+            PlayMode::Once => {
+                if self.index == last {
+                    self.finished = true;
+                } else {
+                    self.index += 1;
+                }
+            }
+            PlayMode::PingPong => {
+                if last == 0 {
+                    return;
+                }
+                if self.forward && self.index == last {
+                    self.forward = false;
+                } else if !self.forward && self.index == 0 {
+                    self.forward = true;
+                }
+                if self.forward {
+                    self.index += 1;
+                } else {
+                    self.index -= 1;
+                }
+            }
+        }
+    }
+
+    // Frames that aren't in the sheet show as nothing rather than panicking mid frame.
+    pub fn frame(&self) -> Frame {
+        let index = self.clip.frames[self.index].0;
+        self.sheet.frame(index).copied().unwrap_or(Frame {
+            uv_rect: [0.0; 4],
+            size: Vector2 { x: 0.0, y: 0.0 },
+        })
+    }
+
+    // Index of the current frame in the sheet.
+    pub fn frame_index(&self) -> usize {
+        self.clip.frames[self.index].0
+    }
+
+    // Only ever true for PlayMode::Once clips, once their last frame has run out.
+    pub fn is_finished(&self) -> bool {
+        self.finished
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FRAME: Duration = Duration::from_millis(100);
+
+    // Sheets are normally cut from a sprite, the frames' rects don't matter here.
+    fn sheet(frames: usize) -> Rc<SpriteSheet> {
+        let frame = Frame {
+            uv_rect: [0.0; 4],
+            size: Vector2 { x: 1.0, y: 1.0 },
+        };
+        Rc::new(SpriteSheet {
+            frames: vec![frame; frames],
+            names: HashMap::new(),
+        })
+    }
+
+    fn animator(mode: PlayMode) -> Animator {
+        Animator::new(sheet(3), Rc::new(AnimationClip::uniform(&[0, 1, 2], FRAME, mode)))
+    }
+
+    // The frame shown after each of the given number of frame lengths, starting with the first.
+    fn play(animator: &mut Animator, frames: usize) -> Vec<usize> {
+        let mut shown = vec![animator.frame_index()];
+        for _ in 0..frames {
+            animator.update(FRAME);
+            shown.push(animator.frame_index());
+        }
+        shown
+    }
+
+    #[test]
+    fn loop_starts_over() {
+        let mut animator = animator(PlayMode::Loop);
+        assert_eq!(play(&mut animator, 5), [0, 1, 2, 0, 1, 2]);
+        assert!(!animator.is_finished());
+    }
+
+    #[test]
+    fn ping_pong_turns_around_without_repeating_the_ends() {
+        let mut animator = animator(PlayMode::PingPong);
+        assert_eq!(play(&mut animator, 6), [0, 1, 2, 1, 0, 1, 2]);
+    }
+
+    #[test]
+    fn once_stops_on_its_last_frame() {
+        let mut animator = animator(PlayMode::Once);
+        assert_eq!(play(&mut animator, 2), [0, 1, 2]);
+        assert!(!animator.is_finished());
+        // the last frame still gets its time before it's done
+        assert!(!animator.update(FRAME));
+        assert!(animator.is_finished());
+        assert!(!animator.update(FRAME * 10));
+        assert_eq!(animator.frame_index(), 2);
+        animator.restart();
+        assert_eq!(animator.frame_index(), 0);
+        assert!(!animator.is_finished());
+    }
+
+    #[test]
+    fn long_updates_skip_frames_and_keep_the_rest() {
+        let mut animator = animator(PlayMode::Loop);
+        assert!(animator.update(FRAME * 2 + FRAME / 2));
+        assert_eq!(animator.frame_index(), 2);
+        // half of frame 2 is already gone
+        assert!(animator.update(FRAME / 2));
+        assert_eq!(animator.frame_index(), 0);
+        assert!(!animator.update(FRAME / 2));
+    }
+
+    #[test]
+    fn zero_length_frames_move_on_one_per_update() {
+        let clip = AnimationClip::uniform(&[0, 1, 2], Duration::ZERO, PlayMode::Loop);
+        let mut animator = Animator::new(sheet(3), Rc::new(clip));
+        assert!(animator.update(Duration::ZERO));
+        assert_eq!(animator.frame_index(), 1);
+        assert!(animator.update(FRAME));
+        assert_eq!(animator.frame_index(), 2);
+    }
+
+    #[test]
+    fn playing_the_same_clip_again_doesnt_restart_it() {
+        let clip = Rc::new(AnimationClip::uniform(&[0, 1, 2], FRAME, PlayMode::Loop));
+        let mut animator = Animator::new(sheet(3), Rc::clone(&clip));
+        animator.update(FRAME);
+        animator.play(Rc::clone(&clip));
+        assert_eq!(animator.frame_index(), 1);
+        animator.play(Rc::new(AnimationClip::uniform(&[2, 1], FRAME, PlayMode::Loop)));
+        assert_eq!(animator.frame_index(), 2);
+    }
+}
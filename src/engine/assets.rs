@@ -0,0 +1,207 @@
+use crate::engine::atlas::{Atlas, AtlasBuilder};
+use crate::engine::error::Result;
+use crate::engine::gpu::Gpu;
+use crate::engine::sprite::Sprite;
+use crate::engine::texture::Texture;
+use std::collections::HashMap;
+use std::ops::Deref;
+use std::rc::Rc;
+
+// Cheap to clone references to loaded assets, every clone counts as a user of the asset.
+#[derive(Clone)]
+pub struct TextureHandle(Rc<Texture>);
+
+#[derive(Clone)]
+pub struct SpriteHandle(Rc<Sprite>);
+
+impl Deref for TextureHandle {
+    type Target = Texture;
+
+    fn deref(&self) -> &Texture {
+        &self.0
+    }
+}
+
+impl Deref for SpriteHandle {
+    type Target = Sprite;
+
+    fn deref(&self) -> &Sprite {
+        &self.0
+    }
+}
+
+impl TextureHandle {
+    pub fn new(texture: Texture) -> Self {
+        TextureHandle(Rc::new(texture))
+    }
+
+    // Whether both handles point at the same loaded texture.
+    pub fn ptr_eq(&self, other: &TextureHandle) -> bool {
+        Rc::ptr_eq(&self.0, &other.0)
+    }
+
+    // Identifies the loaded texture, the same for every handle to it while it stays loaded.
+    pub fn id(&self) -> usize {
+        Rc::as_ptr(&self.0) as usize
+    }
+}
+
+impl SpriteHandle {
+    pub fn new(sprite: Sprite) -> Self {
+        SpriteHandle(Rc::new(sprite))
+    }
+
+    // How many handles to this sprite exist, including this one.
+    pub fn users(&self) -> usize {
+        Rc::strong_count(&self.0)
+    }
+
+    // Whether both handles point at the same loaded sprite.
+    pub fn ptr_eq(&self, other: &SpriteHandle) -> bool {
+        Rc::ptr_eq(&self.0, &other.0)
+    }
+
+    // Identifies the loaded sprite, the same for every handle to it while it stays loaded.
+    pub fn id(&self) -> usize {
+        Rc::as_ptr(&self.0) as usize
+    }
+}
+
+// Loads each file once and hands out handles to it, so every enemy spawned shares one
+// texture upload instead of reading the png off disk again.
+// Assets stay loaded while any handle to them is alive, collect_unused() drops the rest.
+// Sprites are looked up in the loaded atlases first, so packing a set of files into an
+// atlas doesn't change how the game asks for them.
+pub struct AssetManager {
+    textures: HashMap<String, TextureHandle>,
+    sprites: HashMap<String, SpriteHandle>,
+    atlases: Vec<Atlas>,
+}
+
+impl AssetManager {
+    pub fn new() -> Self {
+        AssetManager {
+            textures: HashMap::new(),
+            sprites: HashMap::new(),
+            atlases: Vec::new(),
+        }
+    }
+
+    pub fn texture(&mut self, gpu: &Gpu, filepath: &str) -> Result<TextureHandle> {
+        if let Some(texture) = self.textures.get(filepath) {
+            return Ok(texture.clone());
+        }
+        let texture = Texture::from_file(&gpu.device, &gpu.queue, filepath)?;
+        let handle = TextureHandle::new(texture);
+        self.textures.insert(filepath.to_string(), handle.clone());
+        Ok(handle)
+    }
+
+    pub fn sprite(&mut self, gpu: &Gpu, filepath: &str) -> Result<SpriteHandle> {
+        if let Some(sprite) = self.atlases.iter().find_map(|atlas| atlas.sprite(filepath)) {
+            return Ok(sprite);
+        }
+        if let Some(sprite) = self.sprites.get(filepath) {
+            return Ok(sprite.clone());
+        }
+        let texture = self.texture(gpu, filepath)?;
+        let sprite = Sprite::new(texture, gpu.render_cache.texture_bind_group_layout(), &gpu.device);
+        let handle = SpriteHandle::new(sprite);
+        self.sprites.insert(filepath.to_string(), handle.clone());
+        Ok(handle)
+    }
+
+    // Packs the given files into an atlas, they are then handed out by sprite() as before.
+    pub fn pack(&mut self, gpu: &Gpu, filepaths: &[&str]) -> Result<()> {
+        let mut builder = AtlasBuilder::default();
+        for filepath in filepaths {
+            builder.add_file(filepath)?;
+        }
+        self.atlases.push(builder.build(gpu)?);
+        Ok(())
+    }
+
+    // Loads a precomputed atlas, see Atlas::load.
+    pub fn load_atlas(&mut self, gpu: &Gpu, manifest_path: &str) -> Result<()> {
+        self.atlases.push(Atlas::load(gpu, manifest_path)?);
+        Ok(())
+    }
+
+    // Unloads every asset only the manager itself still holds, returns how many went.
+    // Sprites go first since they keep their texture alive, an atlas goes as a whole once
+    // none of its sprites are used.
+    pub fn collect_unused(&mut self) -> usize {
+        let before = self.sprites.len() + self.textures.len() + self.atlases.len();
+        self.sprites.retain(|_, sprite| sprite.users() > 1);
+        self.textures.retain(|_, texture| Rc::strong_count(&texture.0) > 1);
+        self.atlases.retain(Atlas::in_use);
+        before - (self.sprites.len() + self.textures.len() + self.atlases.len())
+    }
+
+    pub fn loaded_textures(&self) -> usize {
+        self.textures.len()
+    }
+
+    pub fn loaded_sprites(&self) -> usize {
+        self.sprites.len()
+    }
+
+    pub fn loaded_atlases(&self) -> usize {
+        self.atlases.len()
+    }
+}
+
+impl Default for AssetManager {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::headless;
+
+    const PATH: &str = "assets/enemy.png";
+
+    #[test]
+    fn loading_a_path_twice_shares_one_handle() {
+        let gpu = match headless::for_tests(4, 4) {
+            Some(headless) => headless.gpu,
+            None => return,
+        };
+        let mut assets = AssetManager::new();
+        let first = assets.sprite(&gpu, PATH).unwrap();
+        let second = assets.sprite(&gpu, PATH).unwrap();
+        assert!(first.ptr_eq(&second));
+        assert_eq!(first.id(), second.id());
+        assert_eq!(assets.loaded_sprites(), 1);
+        assert_eq!(assets.loaded_textures(), 1);
+        // the two handles and the manager's own
+        assert_eq!(first.users(), 3);
+    }
+
+    #[test]
+    fn unused_assets_are_freed() {
+        let gpu = match headless::for_tests(4, 4) {
+            Some(headless) => headless.gpu,
+            None => return,
+        };
+        let mut assets = AssetManager::new();
+        let sprite = assets.sprite(&gpu, PATH).unwrap();
+        let texture = Rc::downgrade(&assets.texture(&gpu, PATH).unwrap().0);
+        // still held, nothing goes
+        assert_eq!(assets.collect_unused(), 0);
+        assert_eq!(assets.loaded_sprites(), 1);
+        assert!(texture.upgrade().is_some());
+
+        drop(sprite);
+        assert_eq!(assets.collect_unused(), 2);
+        assert_eq!(assets.loaded_sprites(), 0);
+        assert_eq!(assets.loaded_textures(), 0);
+        assert!(texture.upgrade().is_none());
+        // and loading it again makes a fresh one
+        let again = assets.sprite(&gpu, PATH).unwrap();
+        assert_eq!(again.users(), 2);
+    }
+}
@@ -0,0 +1,393 @@
+use crate::engine::assets::{SpriteHandle, TextureHandle};
+use crate::engine::error::{EngineError, Result};
+use crate::engine::gpu::Gpu;
+use crate::engine::sprite::Sprite;
+use crate::engine::texture::Texture;
+use image::RgbaImage;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+use std::rc::Rc;
+
+// Largest page the builder makes, every gpu we target supports at least this.
+pub const DEFAULT_PAGE_SIZE: u32 = 2048;
+
+// Where one image ended up, in pixels of its page.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AtlasRegion {
+    pub page: usize,
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+// The file that goes with a precomputed atlas, written in RON:
+// (
+//     pages: ["sprites_0.png"],
+//     regions: {
+//         "assets/player.png": (page: 0, x: 0, y: 0, width: 32, height: 32),
+//     },
+// )
+// Page paths are relative to the manifest.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct AtlasManifest {
+    pub pages: Vec<String>,
+    pub regions: BTreeMap<String, AtlasRegion>,
+}
+
+// Packs images into as few pages as it can, tallest first along shelves.
+pub struct AtlasBuilder {
+    page_size: u32,
+    // empty pixels left around each image so neighbours never bleed into each other
+    padding: u32,
+    images: Vec<(String, RgbaImage)>,
+}
+
+// Packed pages still in memory, ready to be uploaded or saved as a precomputed atlas.
+pub struct PackedAtlas {
+    pub pages: Vec<RgbaImage>,
+    pub regions: BTreeMap<String, AtlasRegion>,
+}
+
+// Textures of every page plus a sprite for each region. All sprites on a page share one
+// bind group, so the batch draws them in a single call.
+pub struct Atlas {
+    pages: Vec<TextureHandle>,
+    sprites: HashMap<String, SpriteHandle>,
+}
+
+impl AtlasBuilder {
+    pub fn new(page_size: u32) -> Self {
+        AtlasBuilder {
+            page_size,
+            padding: 1,
+            images: Vec::new(),
+        }
+    }
+
+    pub fn set_padding(&mut self, padding: u32) {
+        self.padding = padding;
+    }
+
+    // The region is named after the path, so it can be looked up the same way a loose
+    // file would be.
+    pub fn add_file(&mut self, path: &str) -> Result<()> {
+        let bytes = std::fs::read(path).map_err(|source| EngineError::Io {
+            path: path.to_string(),
+            source,
+        })?;
+        let image = image::load_from_memory(&bytes).map_err(|source| EngineError::Decode {
+            path: path.to_string(),
+            source,
+        })?;
+        self.add_image(path, image.to_rgba8());
+        Ok(())
+    }
+
+    // Adding a name twice replaces the earlier image.
+    pub fn add_image(&mut self, name: &str, image: RgbaImage) {
+        match self.images.iter_mut().find(|(existing, _)| existing == name) {
+            Some(entry) => entry.1 = image,
+            None => self.images.push((name.to_string(), image)),
+        }
+    }
+
+    pub fn pack(mut self) -> Result<PackedAtlas> {
+        // tallest first keeps each shelf from wasting much space above its shorter images
+        self.images
+            .sort_by(|(_, a), (_, b)| b.height().cmp(&a.height()).then(b.width().cmp(&a.width())));
+
+        // used width and height of each page, pages are cropped to it
+        let mut extents: Vec<(u32, u32)> = Vec::new();
+        let mut regions = BTreeMap::new();
+        let (mut x, mut y, mut shelf_height) = (0, 0, 0);
+        for (name, image) in &self.images {
+            let (width, height) = image.dimensions();
+            if width > self.page_size || height > self.page_size {
+                return Err(EngineError::ImageTooLarge {
+                    name: name.clone(),
+                    page_size: self.page_size,
+                });
+            }
+            if extents.is_empty() {
+                extents.push((0, 0));
+            }
+            if x + width > self.page_size {
+                // next shelf
+                x = 0;
+                y += shelf_height;
+                shelf_height = 0;
+            }
+            if y + height > self.page_size {
+                // next page
+                extents.push((0, 0));
+                x = 0;
+                y = 0;
+                shelf_height = 0;
+            }
+            let page = extents.len() - 1;
+            regions.insert(
+                name.clone(),
+                AtlasRegion {
+                    page,
+                    x,
+                    y,
+                    width,
+                    height,
+                },
+            );
+            let extent = &mut extents[page];
+            extent.0 = extent.0.max(x + width);
+            extent.1 = extent.1.max(y + height);
+            x += width + self.padding;
+            shelf_height = shelf_height.max(height + self.padding);
+        }
+
+        let mut pages: Vec<RgbaImage> = extents
+            .iter()
+            .map(|&(width, height)| RgbaImage::new(width.max(1), height.max(1)))
+            .collect();
+        for (name, image) in &self.images {
+            let region = &regions[name];
+            image::imageops::replace(&mut pages[region.page], image, region.x as i64, region.y as i64);
+        }
+        Ok(PackedAtlas { pages, regions })
+    }
+
+    pub fn build(self, gpu: &Gpu) -> Result<Atlas> {
+        self.pack()?.upload(gpu)
+    }
+}
+
+impl Default for AtlasBuilder {
+    fn default() -> Self {
+        Self::new(DEFAULT_PAGE_SIZE)
+    }
+}
+
+impl PackedAtlas {
+    pub fn upload(self, gpu: &Gpu) -> Result<Atlas> {
+        let mut pages = Vec::new();
+        for (index, page) in self.pages.into_iter().enumerate() {
+            let label = format!("atlas page {}", index);
+            let image = image::DynamicImage::ImageRgba8(page);
+            let texture = Texture::from_image(&gpu.device, &gpu.queue, &image, Some(&label))?;
+            pages.push(TextureHandle::new(texture));
+        }
+        Atlas::from_pages(gpu, pages, &self.regions, "packed atlas")
+    }
+
+    // Writes name_0.png, name_1.png... and name.ron into dir, ready for Atlas::load.
+    pub fn save(&self, dir: &str, name: &str) -> Result<()> {
+        let mut manifest = AtlasManifest {
+            pages: Vec::new(),
+            regions: self.regions.clone(),
+        };
+        for (index, page) in self.pages.iter().enumerate() {
+            let file_name = format!("{}_{}.png", name, index);
+            let path = Path::new(dir).join(&file_name);
+            page.save(&path).map_err(|source| EngineError::Encode {
+                path: path.display().to_string(),
+                source,
+            })?;
+            manifest.pages.push(file_name);
+        }
+        let path = Path::new(dir).join(format!("{}.ron", name));
+        let text = ron::ser::to_string_pretty(&manifest, ron::ser::PrettyConfig::default()).map_err(|err| {
+            EngineError::Manifest {
+                path: path.display().to_string(),
+                message: err.to_string(),
+            }
+        })?;
+        std::fs::write(&path, text).map_err(|source| EngineError::Io {
+            path: path.display().to_string(),
+            source,
+        })
+    }
+}
+
+impl Atlas {
+    // Loads a precomputed atlas from its RON manifest and page images.
+    pub fn load(gpu: &Gpu, manifest_path: &str) -> Result<Self> {
+        let text = std::fs::read_to_string(manifest_path).map_err(|source| EngineError::Io {
+            path: manifest_path.to_string(),
+            source,
+        })?;
+        let manifest: AtlasManifest = ron::from_str(&text).map_err(|err| EngineError::Manifest {
+            path: manifest_path.to_string(),
+            message: err.to_string(),
+        })?;
+        let dir = Path::new(manifest_path).parent().unwrap_or_else(|| Path::new(""));
+        let mut pages = Vec::new();
+        for page in &manifest.pages {
+            let path = dir.join(page).display().to_string();
+            let texture = Texture::from_file(&gpu.device, &gpu.queue, &path)?;
+            pages.push(TextureHandle::new(texture));
+        }
+        Atlas::from_pages(gpu, pages, &manifest.regions, manifest_path)
+    }
+
+    fn from_pages(
+        gpu: &Gpu,
+        pages: Vec<TextureHandle>,
+        regions: &BTreeMap<String, AtlasRegion>,
+        label: &str,
+    ) -> Result<Self> {
+        let layout = gpu.render_cache.texture_bind_group_layout();
+        let bind_groups: Vec<Rc<wgpu::BindGroup>> = pages
+            .iter()
+            .map(|page| Rc::new(Sprite::create_bind_group(page, layout, &gpu.device)))
+            .collect();
+        let mut sprites = HashMap::new();
+        for (name, region) in regions {
+            let page = pages.get(region.page).filter(|page| {
+                region.x + region.width <= page.texture.width() && region.y + region.height <= page.texture.height()
+            });
+            let page = page.ok_or_else(|| EngineError::Manifest {
+                path: label.to_string(),
+                message: format!("region {} is outside of its page", name),
+            })?;
+            let sprite = Sprite::from_region(
+                page.clone(),
+                bind_groups[region.page].clone(),
+                region.x,
+                region.y,
+                region.width,
+                region.height,
+            );
+            sprites.insert(name.clone(), SpriteHandle::new(sprite));
+        }
+        Ok(Atlas { pages, sprites })
+    }
+
+    pub fn sprite(&self, name: &str) -> Option<SpriteHandle> {
+        self.sprites.get(name).cloned()
+    }
+
+    pub fn page_count(&self) -> usize {
+        self.pages.len()
+    }
+
+    pub fn sprite_count(&self) -> usize {
+        self.sprites.len()
+    }
+
+    // Whether anything outside the atlas still holds one of its sprites.
+    pub fn in_use(&self) -> bool {
+        self.sprites.values().any(|sprite| sprite.users() > 1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::headless;
+    use image::Rgba;
+
+    fn square(size: u32, shade: u8) -> RgbaImage {
+        RgbaImage::from_pixel(size, size, Rgba([shade, 0, 0, 255]))
+    }
+
+    fn overlap(a: &AtlasRegion, b: &AtlasRegion) -> bool {
+        a.page == b.page && a.x < b.x + b.width && b.x < a.x + a.width && a.y < b.y + b.height && b.y < a.y + a.height
+    }
+
+    #[test]
+    fn full_pages_overflow_into_the_next() {
+        // two 30px squares fit side by side on a 64px page and two shelves high
+        let mut builder = AtlasBuilder::new(64);
+        for i in 0..5 {
+            builder.add_image(&format!("square {}", i), square(30, i as u8));
+        }
+        let packed = builder.pack().unwrap();
+        assert_eq!(packed.pages.len(), 2);
+        assert_eq!(packed.regions.values().filter(|region| region.page == 0).count(), 4);
+        // cropped to what's used
+        assert_eq!(packed.pages[0].dimensions(), (61, 61));
+        assert_eq!(packed.pages[1].dimensions(), (30, 30));
+        let regions: Vec<_> = packed.regions.values().collect();
+        for (i, a) in regions.iter().enumerate() {
+            assert!(a.x + a.width <= 64 && a.y + a.height <= 64);
+            assert!(regions[i + 1..].iter().all(|b| !overlap(a, b)));
+        }
+    }
+
+    #[test]
+    fn images_are_copied_into_their_regions() {
+        let mut builder = AtlasBuilder::new(64);
+        builder.add_image("dark", square(8, 10));
+        builder.add_image("light", square(16, 200));
+        let packed = builder.pack().unwrap();
+        for (name, shade) in [("dark", 10), ("light", 200)] {
+            let region = packed.regions[name];
+            let page = &packed.pages[region.page];
+            assert_eq!(page.get_pixel(region.x, region.y).0[0], shade);
+            assert_eq!(page.get_pixel(region.x + region.width - 1, region.y + region.height - 1).0[0], shade);
+        }
+    }
+
+    #[test]
+    fn images_bigger_than_a_page_are_an_error() {
+        let mut builder = AtlasBuilder::new(16);
+        builder.add_image("huge", square(17, 0));
+        assert!(matches!(builder.pack(), Err(EngineError::ImageTooLarge { .. })));
+    }
+
+    #[test]
+    fn sprites_get_their_region_as_uvs() {
+        let gpu = match headless::for_tests(4, 4) {
+            Some(headless) => headless.gpu,
+            None => return,
+        };
+        let mut builder = AtlasBuilder::new(64);
+        builder.add_image("big", square(32, 0));
+        builder.add_image("small", square(16, 0));
+        let packed = builder.pack().unwrap();
+        let regions = packed.regions.clone();
+        let (page_width, page_height) = packed.pages[0].dimensions();
+        let atlas = packed.upload(&gpu).unwrap();
+        for name in ["big", "small"] {
+            let region = regions[name];
+            let sprite = atlas.sprite(name).unwrap();
+            let expected = [
+                region.x as f32 / page_width as f32,
+                region.y as f32 / page_height as f32,
+                region.width as f32 / page_width as f32,
+                region.height as f32 / page_height as f32,
+            ];
+            assert_eq!(sprite.uv_rect, expected);
+            assert_eq!(sprite.size, cgmath::Vector2::new(region.width as f32, region.height as f32));
+        }
+    }
+
+    #[test]
+    fn saved_atlases_load_back_the_same() {
+        let gpu = match headless::for_tests(4, 4) {
+            Some(headless) => headless.gpu,
+            None => return,
+        };
+        let dir = std::env::temp_dir().join(format!("atlas_round_trip_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let dir = dir.to_str().unwrap();
+        let mut builder = AtlasBuilder::new(32);
+        builder.add_image("a", square(20, 0));
+        builder.add_image("b", square(20, 0));
+        let packed = builder.pack().unwrap();
+        packed.save(dir, "sprites").unwrap();
+
+        let manifest_path = format!("{}/sprites.ron", dir);
+        let manifest: AtlasManifest = ron::from_str(&std::fs::read_to_string(&manifest_path).unwrap()).unwrap();
+        assert_eq!(manifest.pages, vec!["sprites_0.png", "sprites_1.png"]);
+        assert_eq!(manifest.regions, packed.regions);
+
+        let loaded = Atlas::load(&gpu, &manifest_path).unwrap();
+        let uploaded = packed.upload(&gpu).unwrap();
+        assert_eq!(loaded.page_count(), 2);
+        assert_eq!(loaded.sprite_count(), 2);
+        for name in ["a", "b"] {
+            assert_eq!(loaded.sprite(name).unwrap().uv_rect, uploaded.sprite(name).unwrap().uv_rect);
+        }
+        std::fs::remove_dir_all(dir).ok();
+    }
+}
@@ -0,0 +1,28 @@
+// Draws the virtual canvas over the whole viewport, the letterbox comes from the viewport
+// set on the render pass.
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) tex_coords: vec2<f32>,
+}
+
+// One triangle big enough to cover the screen, no vertex buffer needed.
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> VertexOutput {
+    var out: VertexOutput;
+    let corner = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+    out.clip_position = vec4<f32>(corner * 2.0 - 1.0, 0.0, 1.0);
+    // textures start at the top, clip space at the bottom
+    out.tex_coords = vec2<f32>(corner.x, 1.0 - corner.y);
+    return out;
+}
+
+@group(0) @binding(0)
+var t_canvas: texture_2d<f32>;
+@group(0) @binding(1)
+var s_canvas: sampler;
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    return textureSample(t_canvas, s_canvas, in.tex_coords);
+}
@@ -0,0 +1,135 @@
+use cgmath::prelude::*;
+use cgmath::{Deg, Matrix4, Vector2, Vector3, Vector4};
+use rand::Rng;
+use std::time::Duration;
+
+#[repr(C)]
+#[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct CameraUniform {
+    view_proj: [[f32; 4]; 4],
+}
+
+// What part of the world gets drawn. position is the world point at the centre of the
+// viewport, y goes up like the rest of the engine. The viewport is the virtual canvas, so it
+// stays the same size however the window is resized.
+pub struct Camera2D {
+    pub position: Vector2<f32>,
+    pub zoom: f32,
+    // in degrees like entities
+    pub rotation: f32,
+    viewport: Vector2<f32>,
+    shake_intensity: f32,
+    shake_duration: Duration,
+    shake_remaining: Duration,
+    shake_offset: Vector2<f32>,
+    follow_target: Option<Vector2<f32>>,
+    // how quickly the camera catches up with the target, higher is snappier
+    follow_speed: f32,
+}
+
+impl Camera2D {
+    // A camera looking at the viewport's own area, world and screen pixels line up.
+    pub fn new(width: u32, height: u32) -> Self {
+        let viewport = Vector2 {
+            x: width as f32,
+            y: height as f32,
+        };
+        Camera2D {
+            position: viewport / 2.0,
+            zoom: 1.0,
+            rotation: 0.0,
+            viewport,
+            shake_intensity: 0.0,
+            shake_duration: Duration::ZERO,
+            shake_remaining: Duration::ZERO,
+            shake_offset: Vector2 { x: 0.0, y: 0.0 },
+            follow_target: None,
+            follow_speed: 5.0,
+        }
+    }
+
+    pub fn viewport(&self) -> Vector2<f32> {
+        self.viewport
+    }
+
+    // Jitters the view by up to intensity pixels, fading out over duration.
+    // A stronger shake replaces a weaker one that's still going.
+    pub fn shake(&mut self, intensity: f32, duration: Duration) {
+        if self.shake_remaining.is_zero() || intensity >= self.current_shake() {
+            self.shake_intensity = intensity;
+            self.shake_duration = duration;
+            self.shake_remaining = duration;
+        }
+    }
+
+    fn current_shake(&self) -> f32 {
+        if self.shake_duration.is_zero() {
+            return 0.0;
+        }
+        self.shake_intensity * (self.shake_remaining.as_secs_f32() / self.shake_duration.as_secs_f32())
+    }
+
+    // The camera eases towards the target every update, set it each tick to follow
+    // something that moves. None stops following.
+    pub fn follow(&mut self, target: Option<Vector2<f32>>) {
+        self.follow_target = target;
+    }
+
+    pub fn set_follow_speed(&mut self, follow_speed: f32) {
+        self.follow_speed = follow_speed;
+    }
+
+    // Called once per tick by the engine.
+    pub fn update(&mut self, dt: Duration) {
+        if let Some(target) = self.follow_target {
+            // frame rate independent easing, covers the same share of the distance per second
+            let t = 1.0 - (-self.follow_speed * dt.as_secs_f32()).exp();
+            self.position = self.position.lerp(target, t);
+        }
+        self.shake_remaining = self.shake_remaining.saturating_sub(dt);
+        let strength = self.current_shake();
+        self.shake_offset = if strength > 0.0 {
+            let mut rng = rand::thread_rng();
+            Vector2 {
+                x: rng.gen_range(-1.0..=1.0) * strength,
+                y: rng.gen_range(-1.0..=1.0) * strength,
+            }
+        } else {
+            Vector2 { x: 0.0, y: 0.0 }
+        };
+    }
+
+    // World to clip space, z is left alone.
+    pub fn view_projection(&self) -> Matrix4<f32> {
+        let centre = self.position + self.shake_offset;
+        let projection = Matrix4::from_nonuniform_scale(2.0 / self.viewport.x, 2.0 / self.viewport.y, 1.0);
+        let view = Matrix4::from_angle_z(Deg(-self.rotation))
+            * Matrix4::from_nonuniform_scale(self.zoom, self.zoom, 1.0)
+            * Matrix4::from_translation(Vector3 {
+                x: -centre.x,
+                y: -centre.y,
+                z: 0.0,
+            });
+        projection * view
+    }
+
+    pub fn to_uniform(&self) -> CameraUniform {
+        CameraUniform {
+            view_proj: self.view_projection().into(),
+        }
+    }
+
+    // Turns a point in viewport pixels (origin top left, y down like window events) into
+    // the world point drawn there.
+    pub fn screen_to_world(&self, x: f32, y: f32) -> Vector2<f32> {
+        let clip = Vector4 {
+            x: x / self.viewport.x * 2.0 - 1.0,
+            y: 1.0 - y / self.viewport.y * 2.0,
+            z: 0.0,
+            w: 1.0,
+        };
+        let inverse = self.view_projection().invert().unwrap_or_else(Matrix4::identity);
+        let world = inverse * clip;
+        Vector2 { x: world.x, y: world.y }
+    }
+}
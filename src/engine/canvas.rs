@@ -0,0 +1,274 @@
+use crate::engine::gpu::Gpu;
+use crate::engine::texture::Texture;
+use cgmath::Vector2;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ScalingMode {
+    // whole multiples of the virtual size only, every pixel stays the same size
+    Integer,
+    // as large as fits in the window
+    Fit,
+}
+
+// Where the virtual canvas ends up in the window, everything around it is letterbox bars.
+#[derive(Clone, Copy, Debug)]
+pub struct Letterbox {
+    virtual_size: Vector2<f32>,
+    scaling: ScalingMode,
+    // x, y, width, height in window pixels, origin top left
+    rect: [f32; 4],
+}
+
+impl Letterbox {
+    pub fn new(virtual_width: u32, virtual_height: u32, scaling: ScalingMode) -> Self {
+        let mut letterbox = Letterbox {
+            virtual_size: Vector2 {
+                x: virtual_width as f32,
+                y: virtual_height as f32,
+            },
+            scaling,
+            rect: [0.0, 0.0, virtual_width as f32, virtual_height as f32],
+        };
+        letterbox.resize(virtual_width, virtual_height);
+        letterbox
+    }
+
+    pub fn resize(&mut self, window_width: u32, window_height: u32) {
+        let window_width = window_width as f32;
+        let window_height = window_height as f32;
+        let fit = (window_width / self.virtual_size.x).min(window_height / self.virtual_size.y);
+        let scale = match self.scaling {
+            // a window smaller than the canvas still gets the whole canvas, just not crisp
+            ScalingMode::Integer if fit >= 1.0 => fit.floor(),
+            _ => fit,
+        };
+        let width = (self.virtual_size.x * scale).round();
+        let height = (self.virtual_size.y * scale).round();
+        self.rect = [
+            ((window_width - width) / 2.0).floor(),
+            ((window_height - height) / 2.0).floor(),
+            width,
+            height,
+        ];
+    }
+
+    pub fn set_scaling(&mut self, scaling: ScalingMode, window_width: u32, window_height: u32) {
+        self.scaling = scaling;
+        self.resize(window_width, window_height);
+    }
+
+    pub fn scaling(&self) -> ScalingMode {
+        self.scaling
+    }
+
+    pub fn rect(&self) -> [f32; 4] {
+        self.rect
+    }
+
+    // Window pixels to canvas pixels, both with the origin top left like window events.
+    // None when the point is on the bars.
+    pub fn window_to_canvas(&self, x: f32, y: f32) -> Option<Vector2<f32>> {
+        let [left, top, width, height] = self.rect;
+        if x < left || y < top || x >= left + width || y >= top + height {
+            return None;
+        }
+        Some(Vector2 {
+            x: (x - left) / width * self.virtual_size.x,
+            y: (y - top) / height * self.virtual_size.y,
+        })
+    }
+}
+
+// The offscreen target every frame is drawn into at the virtual resolution, then scaled
+// onto the window by blit().
+pub struct VirtualCanvas {
+    texture: Texture,
+    bind_group: wgpu::BindGroup,
+    pipeline: wgpu::RenderPipeline,
+}
+
+impl VirtualCanvas {
+    // Uses the gpu's format, the same as the window surface, so the sprite pipeline can
+    // draw to either.
+    pub fn new(gpu: &Gpu, width: u32, height: u32) -> Self {
+        let device = &gpu.device;
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Virtual Canvas"),
+            size: wgpu::Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: gpu.format,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+            view_formats: &[],
+        });
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        // nearest so pixel art stays crisp
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Nearest,
+            min_filter: wgpu::FilterMode::Nearest,
+            mipmap_filter: wgpu::FilterMode::Nearest,
+            ..Default::default()
+        });
+        let layout = gpu.render_cache.texture_bind_group_layout();
+        let texture = Texture {
+            texture,
+            view,
+            sampler,
+        };
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&texture.view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(&texture.sampler),
+                },
+            ],
+            label: Some("canvas_bind_group"),
+        });
+
+        let shader = device.create_shader_module(wgpu::include_wgsl!("blit.wgsl"));
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Blit Pipeline Layout"),
+            bind_group_layouts: &[layout],
+            push_constant_ranges: &[],
+        });
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Blit Pipeline"),
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: "vs_main",
+                buffers: &[],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: "fs_main",
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: gpu.format,
+                    blend: None,
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+            }),
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+        });
+
+        VirtualCanvas {
+            texture,
+            bind_group,
+            pipeline,
+        }
+    }
+
+    pub fn view(&self) -> &wgpu::TextureView {
+        &self.texture.view
+    }
+
+    pub fn width(&self) -> u32 {
+        self.texture.texture.width()
+    }
+
+    pub fn height(&self) -> u32 {
+        self.texture.texture.height()
+    }
+
+    // Draws the canvas into target where the letterbox says and clears the bars to black.
+    pub fn blit(&self, encoder: &mut wgpu::CommandEncoder, target: &wgpu::TextureView, letterbox: &Letterbox) {
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Blit Pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: target,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                    store: true,
+                },
+            })],
+            depth_stencil_attachment: None,
+        });
+        let [x, y, width, height] = letterbox.rect();
+        render_pass.set_viewport(x, y, width, height, 0.0, 1.0);
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(0, &self.bind_group, &[]);
+        render_pass.draw(0..3, 0..1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WIDTH: u32 = 320;
+    const HEIGHT: u32 = 240;
+
+    fn letterbox(scaling: ScalingMode, window_width: u32, window_height: u32) -> Letterbox {
+        let mut letterbox = Letterbox::new(WIDTH, HEIGHT, scaling);
+        letterbox.resize(window_width, window_height);
+        letterbox
+    }
+
+    #[test]
+    fn fit_fills_the_window_one_way() {
+        // 2.91x fits the height, bars left and right
+        assert_eq!(letterbox(ScalingMode::Fit, 1000, 700).rect(), [33.0, 0.0, 933.0, 700.0]);
+        assert_eq!(letterbox(ScalingMode::Fit, 641, 999).rect(), [0.0, 259.0, 641.0, 481.0]);
+    }
+
+    #[test]
+    fn integer_only_uses_whole_multiples() {
+        assert_eq!(letterbox(ScalingMode::Integer, 1000, 700).rect(), [180.0, 110.0, 640.0, 480.0]);
+        // an odd pixel left over goes to the right and bottom bars
+        assert_eq!(letterbox(ScalingMode::Integer, 643, 481).rect(), [1.0, 0.0, 640.0, 480.0]);
+        // just short of 3x is still 2x
+        assert_eq!(letterbox(ScalingMode::Integer, 959, 719).rect(), [159.0, 119.0, 640.0, 480.0]);
+    }
+
+    #[test]
+    fn integer_shrinks_to_fit_small_windows() {
+        assert_eq!(letterbox(ScalingMode::Integer, 200, 150).rect(), [0.0, 0.0, 200.0, 150.0]);
+    }
+
+    #[test]
+    fn switching_scaling_resizes() {
+        let mut letterbox = letterbox(ScalingMode::Fit, 1000, 700);
+        letterbox.set_scaling(ScalingMode::Integer, 1000, 700);
+        assert_eq!(letterbox.scaling(), ScalingMode::Integer);
+        assert_eq!(letterbox.rect(), [180.0, 110.0, 640.0, 480.0]);
+    }
+
+    #[test]
+    fn window_centre_is_canvas_centre() {
+        let integer = letterbox(ScalingMode::Integer, 1000, 700);
+        assert_eq!(integer.window_to_canvas(500.0, 350.0), Some(Vector2 { x: 160.0, y: 120.0 }));
+        assert_eq!(integer.window_to_canvas(180.0, 110.0), Some(Vector2 { x: 0.0, y: 0.0 }));
+        // off by at most the rounding of the canvas size
+        let centre = letterbox(ScalingMode::Fit, 1001, 701).window_to_canvas(500.5, 350.5).unwrap();
+        assert!((centre.x - 160.0).abs() < 0.5 && (centre.y - 120.0).abs() < 0.5, "{:?}", centre);
+    }
+
+    #[test]
+    fn points_on_the_bars_are_none() {
+        let letterbox = letterbox(ScalingMode::Integer, 1000, 700);
+        assert_eq!(letterbox.window_to_canvas(10.0, 350.0), None);
+        assert_eq!(letterbox.window_to_canvas(179.9, 350.0), None);
+        assert_eq!(letterbox.window_to_canvas(500.0, 109.0), None);
+        // the rect's right and bottom edges are already outside
+        assert_eq!(letterbox.window_to_canvas(820.0, 350.0), None);
+        assert_eq!(letterbox.window_to_canvas(500.0, 590.0), None);
+        assert!(letterbox.window_to_canvas(819.9, 589.9).is_some());
+    }
+}
@@ -1,42 +1,701 @@
-use cgmath::Vector2;
+use crate::engine::transformation::Affine2;
+use cgmath::prelude::*;
+use cgmath::{Deg, Vector2};
+use std::collections::HashMap;
+use std::hash::Hash;
 
-// Rectangular bonding box represented by an origin, width, and height
+// The shape of a hitbox. Coordinates are pixels around the entity's origin before its
+// transform, Collision2D moves the shape to wherever the entity is.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Collider {
+    Circle {
+        center: Vector2<f32>,
+        radius: f32,
+    },
+    Aabb {
+        min: Vector2<f32>,
+        max: Vector2<f32>,
+    },
+    // rotation in degrees, clockwise like Transformation
+    Obb {
+        center: Vector2<f32>,
+        half_extents: Vector2<f32>,
+        rotation: f32,
+    },
+    // a line from a to b with round ends, e.g. a laser
+    Capsule {
+        a: Vector2<f32>,
+        b: Vector2<f32>,
+        radius: f32,
+    },
+    // has to be convex, the winding doesn't matter. An empty one never collides.
+    Polygon(Vec<Vector2<f32>>),
+}
+
+// How two overlapping colliders touch. Moving the second one depth pixels along normal
+// pushes them apart.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Contact {
+    // unit length, points from the first collider towards the second
+    pub normal: Vector2<f32>,
+    pub depth: f32,
+}
+
+impl Collider {
+    // The box a sprite of this size is drawn in.
+    pub fn sprite_box(size: Vector2<f32>) -> Self {
+        Collider::Aabb {
+            min: -size / 2.0,
+            max: size / 2.0,
+        }
+    }
+
+    // The same shape after the transform. Boxes turn into polygons since they may not be
+    // axis aligned anymore, circles and capsules keep their shape so the radius is scaled
+    // by the transform's largest scale.
+    pub fn transform(&self, transform: &Affine2) -> Collider {
+        let scale = transform.x_axis.magnitude().max(transform.y_axis.magnitude());
+        match self {
+            Collider::Circle { center, radius } => Collider::Circle {
+                center: transform.transform_point(*center),
+                radius: radius * scale,
+            },
+            Collider::Capsule { a, b, radius } => Collider::Capsule {
+                a: transform.transform_point(*a),
+                b: transform.transform_point(*b),
+                radius: radius * scale,
+            },
+            _ => {
+                let (points, _) = self.hull();
+                Collider::Polygon(points.into_iter().map(|point| transform.transform_point(point)).collect())
+            }
+        }
+    }
+
+    // Smallest axis aligned box around the shape, as its min and max corners.
+    pub fn bounds(&self) -> (Vector2<f32>, Vector2<f32>) {
+        let (points, radius) = self.hull();
+        let mut min = Vector2 { x: f32::INFINITY, y: f32::INFINITY };
+        let mut max = Vector2 { x: f32::NEG_INFINITY, y: f32::NEG_INFINITY };
+        for point in points {
+            min.x = min.x.min(point.x - radius);
+            min.y = min.y.min(point.y - radius);
+            max.x = max.x.max(point.x + radius);
+            max.y = max.y.max(point.y + radius);
+        }
+        (min, max)
+    }
+
+    // Separating axis test between two shapes in the same space, None if they don't
+    // overlap. Shapes that only touch don't overlap, and neither does an empty polygon.
+    pub fn contact(&self, other: &Collider) -> Option<Contact> {
+        let (a, a_radius) = self.hull();
+        let (b, b_radius) = other.hull();
+        if a.is_empty() || b.is_empty() {
+            return None;
+        }
+        let radius = a_radius + b_radius;
+
+        // Every shape is a point, line or polygon grown by a radius. The edge normals of
+        // the cores could separate them, and for round shapes so could the line between
+        // the closest points of the cores, e.g. a circle off the corner of a box.
+        let mut axes: Vec<_> = axes(&a).into_iter().chain(axes(&b)).collect();
+        let (from, to) = closest_points(&a, &b);
+        if radius > 0.0 && (to - from).magnitude2() > f32::EPSILON {
+            axes.push((to - from).normalize());
+        }
+        if axes.is_empty() {
+            // two points right on top of each other, any way out will do
+            return (radius > 0.0).then_some(Contact {
+                normal: Vector2 { x: 0.0, y: 1.0 },
+                depth: radius,
+            });
+        }
+
+        // The least the second shape can be pushed along any axis to clear the first.
+        let mut best: Option<(f32, Vector2<f32>)> = None;
+        for axis in axes {
+            let (a_min, a_max) = project(&a, axis);
+            let (b_min, b_max) = project(&b, axis);
+            let forwards = a_max - b_min + radius;
+            let backwards = b_max - a_min + radius;
+            let (depth, normal) = if forwards <= backwards {
+                (forwards, axis)
+            } else {
+                (backwards, -axis)
+            };
+            if depth <= 0.0 {
+                return None;
+            }
+            if best.is_none_or(|(least, _)| depth < least) {
+                best = Some((depth, normal));
+            }
+        }
+        best.map(|(depth, normal)| Contact { normal, depth })
+    }
+
+    // The shape as a point, line or convex polygon plus how far it reaches around that.
+    fn hull(&self) -> (Vec<Vector2<f32>>, f32) {
+        match self {
+            Collider::Circle { center, radius } => (vec![*center], *radius),
+            Collider::Aabb { min, max } => (
+                vec![
+                    *min,
+                    Vector2 { x: max.x, y: min.y },
+                    *max,
+                    Vector2 { x: min.x, y: max.y },
+                ],
+                0.0,
+            ),
+            Collider::Obb {
+                center,
+                half_extents,
+                rotation,
+            } => {
+                let (sin, cos) = Deg(*rotation).sin_cos();
+                let x_axis = Vector2 { x: cos, y: -sin } * half_extents.x;
+                let y_axis = Vector2 { x: sin, y: cos } * half_extents.y;
+                (
+                    vec![
+                        center - x_axis - y_axis,
+                        center + x_axis - y_axis,
+                        center + x_axis + y_axis,
+                        center - x_axis + y_axis,
+                    ],
+                    0.0,
+                )
+            }
+            Collider::Capsule { a, b, radius } => (vec![*a, *b], *radius),
+            Collider::Polygon(points) => (points.clone(), 0.0),
+        }
+    }
+}
+
+// Directions that could separate a core from another one, the normals of its edges.
+// A line also gets its own direction, for when it's lined up with another line.
+fn axes(points: &[Vector2<f32>]) -> Vec<Vector2<f32>> {
+    let edges = match points.len() {
+        0 | 1 => return Vec::new(),
+        2 => vec![points[1] - points[0]],
+        n => (0..n).map(|i| points[(i + 1) % n] - points[i]).collect(),
+    };
+    let mut axes = Vec::new();
+    for edge in edges.into_iter().filter(|edge| edge.magnitude2() > f32::EPSILON) {
+        let edge = edge.normalize();
+        axes.push(Vector2 { x: -edge.y, y: edge.x });
+        if points.len() == 2 {
+            axes.push(edge);
+        }
+    }
+    axes
+}
+
+fn project(points: &[Vector2<f32>], axis: Vector2<f32>) -> (f32, f32) {
+    points.iter().fold((f32::INFINITY, f32::NEG_INFINITY), |(min, max), point| {
+        let distance = point.dot(axis);
+        (min.min(distance), max.max(distance))
+    })
+}
+
+// The closest point of each core to the other, the cores can't be empty.
+fn closest_points(a: &[Vector2<f32>], b: &[Vector2<f32>]) -> (Vector2<f32>, Vector2<f32>) {
+    let mut best = (a[0], b[0]);
+    let mut best_distance = f32::INFINITY;
+    for (start, end) in segments(b) {
+        for &point in a {
+            let closest = closest_on_segment(point, start, end);
+            let distance = (closest - point).magnitude2();
+            if distance < best_distance {
+                best = (point, closest);
+                best_distance = distance;
+            }
+        }
+    }
+    for (start, end) in segments(a) {
+        for &point in b {
+            let closest = closest_on_segment(point, start, end);
+            let distance = (closest - point).magnitude2();
+            if distance < best_distance {
+                best = (closest, point);
+                best_distance = distance;
+            }
+        }
+    }
+    best
+}
+
+// The edges of a core, a point is an edge that starts and ends in the same place.
+fn segments(points: &[Vector2<f32>]) -> Vec<(Vector2<f32>, Vector2<f32>)> {
+    match points.len() {
+        1 => vec![(points[0], points[0])],
+        2 => vec![(points[0], points[1])],
+        n => (0..n).map(|i| (points[i], points[(i + 1) % n])).collect(),
+    }
+}
+
+fn closest_on_segment(point: Vector2<f32>, start: Vector2<f32>, end: Vector2<f32>) -> Vector2<f32> {
+    let line = end - start;
+    let length = line.magnitude2();
+    if length <= f32::EPSILON {
+        return start;
+    }
+    let t = ((point - start).dot(line) / length).clamp(0.0, 1.0);
+    start + line * t
+}
+
+// A hitbox that follows an entity around. Defaults to the box the sprite is drawn in, so
+// scaling or rotating the entity changes its hitbox the same way.
 pub struct Collision2D {
-    origin: Vector2<f32>,
-    width: f32,
-    height: f32,
+    // before the entity's transform
+    shape: Collider,
+    // the shape is the sprite's box, set_size() refits it
+    fitted: bool,
+    // after the transform, what's actually tested
+    world: Collider,
+    min: Vector2<f32>,
+    max: Vector2<f32>,
+    // bits of the layers it's on and the ones it collides with, see interacts_with()
+    layer: u32,
+    mask: u32,
 }
 
 impl Collision2D {
-    pub fn new(origin: Vector2<f32>, width: f32, height: f32, scale: f32) -> Self {
-        let width = width * scale;
-        let height = height * scale;
-        Collision2D {
-            origin,
-            width,
-            height,
+    pub fn new(size: Vector2<f32>, transform: &Affine2) -> Self {
+        let mut collision = Self::with_shape(Collider::sprite_box(size), transform);
+        collision.fitted = true;
+        collision
+    }
+
+    // A hitbox of its own shape, e.g. a small circle in the middle of the player ship.
+    pub fn with_shape(shape: Collider, transform: &Affine2) -> Self {
+        let mut collision = Collision2D {
+            world: shape.clone(),
+            shape,
+            fitted: false,
+            min: Vector2::zero(),
+            max: Vector2::zero(),
+            layer: 1,
+            mask: u32::MAX,
+        };
+        collision.update(transform);
+        collision
+    }
+
+    // Call whenever the transform changes, usually once per tick.
+    pub fn update(&mut self, transform: &Affine2) {
+        self.world = self.shape.transform(transform);
+        (self.min, self.max) = self.world.bounds();
+    }
+
+    // For when what it's wrapped around changes, e.g. a new animation frame. Hitboxes with
+    // a shape of their own keep it.
+    pub fn set_size(&mut self, size: Vector2<f32>, transform: &Affine2) {
+        if self.fitted {
+            self.shape = Collider::sprite_box(size);
         }
+        self.update(transform);
+    }
+
+    pub fn set_shape(&mut self, shape: Collider, transform: &Affine2) {
+        self.shape = shape;
+        self.fitted = false;
+        self.update(transform);
+    }
+
+    // What the layer bits mean is up to the game, e.g. one for the player and one for its
+    // bullets. Everything starts on layer 1 and collides with everything.
+    pub fn set_layers(&mut self, layer: u32, mask: u32) {
+        self.layer = layer;
+        self.mask = mask;
+    }
+
+    pub fn layer(&self) -> u32 {
+        self.layer
     }
 
-    // LIkely only origin will change, scale moved to different method
-    pub fn update(&mut self, origin: Vector2<f32>) {
-        self.origin = origin;
+    pub fn mask(&self) -> u32 {
+        self.mask
     }
 
-    pub fn set_scale(&mut self, scale: f32) {
-        self.width *= scale;
-        self.height *= scale;
+    // Both sides have to want to collide, each one's mask has to have the other's layer.
+    pub fn interacts_with(&self, other: &Collision2D) -> bool {
+        interacts(self.layer, self.mask, other.layer, other.mask)
     }
 
-    // Returns true if the ractangles overlap
+    pub fn shape(&self) -> &Collider {
+        &self.shape
+    }
+
+    // The shape where the entity is now.
+    pub fn world_shape(&self) -> &Collider {
+        &self.world
+    }
+
+    pub fn width(&self) -> f32 {
+        self.max.x - self.min.x
+    }
+
+    pub fn height(&self) -> f32 {
+        self.max.y - self.min.y
+    }
+
+    // Corners of the axis aligned box around the shape.
+    pub fn min(&self) -> Vector2<f32> {
+        self.min
+    }
+
+    pub fn max(&self) -> Vector2<f32> {
+        self.max
+    }
+
+    // Which way and how far the other one would have to move to stop overlapping.
+    pub fn contact(&self, other: &Collision2D) -> Option<Contact> {
+        // the boxes around them are much cheaper to test first
+        let apart = self.max.x <= other.min.x
+            || self.min.x >= other.max.x
+            || self.max.y <= other.min.y
+            || self.min.y >= other.max.y;
+        if apart {
+            return None;
+        }
+        self.world.contact(&other.world)
+    }
+
+    // Returns true if the shapes overlap
     pub fn check_collision(&self, other: &Collision2D) -> bool {
-        let dist_ax = self.width / 2.0;
-        let dist_ay = self.height / 2.0;
-        let dist_bx = other.width / 2.0;
-        let dist_by = other.height / 2.0;
-        (self.origin.x + dist_ax) > (other.origin.x - dist_bx)
-            && (self.origin.x - dist_ax) < (other.origin.x + dist_bx)
-            && (self.origin.y + dist_ay) > (other.origin.y - dist_by)
-            && (self.origin.y - dist_ay) < (other.origin.y + dist_by)
+        self.contact(other).is_some()
+    }
+}
+
+fn interacts(layer_a: u32, mask_a: u32, layer_b: u32, mask_b: u32) -> bool {
+    mask_a & layer_b != 0 && mask_b & layer_a != 0
+}
+
+// Broad-phase for lots of colliders: the world is cut into square cells and every collider
+// is filed under the cells its box touches. Only colliders sharing a cell can overlap, so
+// checking those is close to linear instead of everything against everything.
+// Refill it each tick with clear() and insert(), then check the candidates it hands back
+// with check_collision(). K is whatever identifies a collider to the caller.
+// Colliders whose layers and masks don't interact are never handed back together.
+pub struct SpatialGrid<K> {
+    cell_size: f32,
+    cells: HashMap<(i32, i32), Vec<GridEntry<K>>>,
+}
+
+#[derive(Clone, Copy)]
+struct GridEntry<K> {
+    key: K,
+    layer: u32,
+    mask: u32,
+}
+
+impl<K: Copy + Eq + Hash + Ord> SpatialGrid<K> {
+    // Cells about the size of the most common collider work best, a lot smaller and big
+    // colliders get filed under many cells, a lot bigger and cells get crowded.
+    pub fn new(cell_size: f32) -> Self {
+        SpatialGrid {
+            cell_size: cell_size.max(1.0),
+            cells: HashMap::new(),
+        }
+    }
+
+    // Drops every cell, only cells something is in this tick are kept around.
+    pub fn clear(&mut self) {
+        self.cells.clear();
+    }
+
+    pub fn insert(&mut self, key: K, collider: &Collision2D) {
+        let (min, max) = self.cell_range(collider);
+        for x in min.0..=max.0 {
+            for y in min.1..=max.1 {
+                self.cells
+                    .entry((x, y))
+                    .or_default()
+                    .push(GridEntry {
+                        key,
+                        layer: collider.layer(),
+                        mask: collider.mask(),
+                    });
+            }
+        }
+    }
+
+    // Every pair of colliders that share a cell, each pair once with the smaller key first.
+    pub fn pairs(&self) -> Vec<(K, K)> {
+        let mut pairs = Vec::new();
+        for cell in self.cells.values() {
+            for (i, a) in cell.iter().enumerate() {
+                for b in &cell[i + 1..] {
+                    if a.key != b.key && interacts(a.layer, a.mask, b.layer, b.mask) {
+                        pairs.push((a.key.min(b.key), a.key.max(b.key)));
+                    }
+                }
+            }
+        }
+        // big colliders share more than one cell
+        pairs.sort_unstable();
+        pairs.dedup();
+        pairs
+    }
+
+    // Every collider sharing a cell with one that isn't in the grid, e.g. a bullet checked
+    // against a grid of enemies.
+    pub fn query(&self, collider: &Collision2D) -> Vec<K> {
+        let (min, max) = self.cell_range(collider);
+        let mut found = Vec::new();
+        for x in min.0..=max.0 {
+            for y in min.1..=max.1 {
+                if let Some(cell) = self.cells.get(&(x, y)) {
+                    found.extend(
+                        cell.iter()
+                            .filter(|entry| interacts(collider.layer(), collider.mask(), entry.layer, entry.mask))
+                            .map(|entry| entry.key),
+                    );
+                }
+            }
+        }
+        found.sort_unstable();
+        found.dedup();
+        found
+    }
+
+    fn cell_range(&self, collider: &Collision2D) -> ((i32, i32), (i32, i32)) {
+        let cell = |point: Vector2<f32>| {
+            (
+                (point.x / self.cell_size).floor() as i32,
+                (point.y / self.cell_size).floor() as i32,
+            )
+        };
+        (cell(collider.min()), cell(collider.max()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn v(x: f32, y: f32) -> Vector2<f32> {
+        Vector2 { x, y }
+    }
+
+    fn at(x: f32, y: f32) -> Affine2 {
+        Affine2 {
+            translation: v(x, y),
+            ..Affine2::identity()
+        }
+    }
+
+    // a 10x10 box around the point
+    fn square(x: f32, y: f32) -> Collision2D {
+        Collision2D::new(v(10.0, 10.0), &at(x, y))
+    }
+
+    fn on_layers(x: f32, y: f32, layer: u32, mask: u32) -> Collision2D {
+        let mut collision = square(x, y);
+        collision.set_layers(layer, mask);
+        collision
+    }
+
+    fn circle(x: f32, y: f32, radius: f32) -> Collider {
+        Collider::Circle { center: v(x, y), radius }
+    }
+
+    fn aabb(min_x: f32, min_y: f32, max_x: f32, max_y: f32) -> Collider {
+        Collider::Aabb {
+            min: v(min_x, min_y),
+            max: v(max_x, max_y),
+        }
+    }
+
+    fn capsule(a: (f32, f32), b: (f32, f32), radius: f32) -> Collider {
+        Collider::Capsule {
+            a: v(a.0, a.1),
+            b: v(b.0, b.1),
+            radius,
+        }
+    }
+
+    fn triangle() -> Collider {
+        Collider::Polygon(vec![v(0.0, 0.0), v(10.0, 0.0), v(0.0, 10.0)])
+    }
+
+    fn diagonal() -> Vector2<f32> {
+        v(1.0, 1.0).normalize()
+    }
+
+    // Checks the contact both ways round, the normal flips and the depth stays.
+    fn assert_contact(a: &Collider, b: &Collider, normal: Vector2<f32>, depth: f32) {
+        for (first, second, normal) in [(a, b, normal), (b, a, -normal)] {
+            let contact = first
+                .contact(second)
+                .unwrap_or_else(|| panic!("{:?} and {:?} should touch", first, second));
+            assert!(
+                (contact.normal - normal).magnitude() < 1e-3 && (contact.depth - depth).abs() < 1e-3,
+                "{:?} against {:?}: expected {:?} {}, got {:?}",
+                first,
+                second,
+                normal,
+                depth,
+                contact
+            );
+        }
+    }
+
+    fn assert_apart(a: &Collider, b: &Collider) {
+        assert_eq!(a.contact(b), None, "{:?} and {:?} shouldn't touch", a, b);
+        assert_eq!(b.contact(a), None, "{:?} and {:?} shouldn't touch", b, a);
+    }
+
+    #[test]
+    fn circle_against_circle() {
+        assert_contact(&circle(0.0, 0.0, 2.0), &circle(3.0, 0.0, 2.0), v(1.0, 0.0), 1.0);
+        // only touching
+        assert_apart(&circle(0.0, 0.0, 2.0), &circle(0.0, 4.0, 2.0));
+        // right on top of each other still has a way out
+        let contact = circle(1.0, 1.0, 2.0).contact(&circle(1.0, 1.0, 1.0)).unwrap();
+        assert_eq!(contact.depth, 3.0);
+    }
+
+    #[test]
+    fn circle_against_boxes() {
+        // inside near the right edge, the short way out is right, not up
+        assert_contact(&aabb(-50.0, -50.0, 50.0, 50.0), &circle(45.0, 0.0, 2.0), v(1.0, 0.0), 7.0);
+        // off a corner, pushed out diagonally
+        let corner_gap = 8.0f32.sqrt();
+        assert_contact(&aabb(0.0, 0.0, 10.0, 10.0), &circle(12.0, 12.0, 3.0), diagonal(), 3.0 - corner_gap);
+        assert_apart(&aabb(0.0, 0.0, 10.0, 10.0), &circle(13.0, 13.0, 3.0));
+        // a box turned into a diamond, the circle sits over its top corner
+        let diamond = Collider::Obb {
+            center: v(0.0, 0.0),
+            half_extents: v(5.0, 5.0),
+            rotation: 45.0,
+        };
+        let top = 50.0f32.sqrt();
+        assert_contact(&diamond, &circle(0.0, 8.0, 1.5), v(0.0, 1.0), 1.5 - (8.0 - top));
+    }
+
+    #[test]
+    fn circle_against_capsule_and_polygon() {
+        assert_contact(&capsule((-5.0, 0.0), (5.0, 0.0), 1.0), &circle(0.0, 2.0, 1.5), v(0.0, 1.0), 0.5);
+        assert_apart(&capsule((-5.0, 0.0), (5.0, 0.0), 1.0), &circle(7.0, 0.0, 1.0));
+        // inside the triangle, the long edge is closest
+        let edge_gap = 2.0f32.sqrt();
+        assert_contact(&triangle(), &circle(4.0, 4.0, 1.0), diagonal(), edge_gap + 1.0);
+    }
+
+    #[test]
+    fn box_against_box() {
+        assert_contact(&aabb(0.0, 0.0, 10.0, 10.0), &aabb(8.0, 2.0, 18.0, 8.0), v(1.0, 0.0), 2.0);
+        // a small box inside a big one near its right edge
+        assert_contact(&aabb(-50.0, -50.0, 50.0, 50.0), &aabb(44.0, -1.0, 48.0, 1.0), v(1.0, 0.0), 6.0);
+        assert_apart(&aabb(0.0, 0.0, 10.0, 10.0), &aabb(10.0, 0.0, 20.0, 10.0));
+        // a diamond poking into the top of a box
+        let diamond = Collider::Obb {
+            center: v(0.0, 9.0),
+            half_extents: v(3.0, 3.0),
+            rotation: 45.0,
+        };
+        assert_contact(&aabb(-5.0, -5.0, 5.0, 5.0), &diamond, v(0.0, 1.0), 18.0f32.sqrt() - 4.0);
+    }
+
+    #[test]
+    fn boxes_against_capsules() {
+        assert_contact(&aabb(0.0, 0.0, 10.0, 10.0), &capsule((12.0, -5.0), (12.0, 15.0), 3.0), v(1.0, 0.0), 1.0);
+        assert_apart(&aabb(0.0, 0.0, 10.0, 10.0), &capsule((13.0, 13.0), (20.0, 20.0), 3.0));
+        // entirely inside
+        assert_contact(&aabb(-50.0, -50.0, 50.0, 50.0), &capsule((40.0, -5.0), (40.0, 5.0), 2.0), v(1.0, 0.0), 12.0);
+        let square = Collider::Obb {
+            center: v(0.0, 0.0),
+            half_extents: v(5.0, 5.0),
+            rotation: 0.0,
+        };
+        assert_contact(&square, &capsule((4.0, 0.0), (10.0, 0.0), 1.0), v(1.0, 0.0), 2.0);
+    }
+
+    #[test]
+    fn capsule_against_capsule() {
+        // side by side
+        assert_contact(&capsule((-5.0, 0.0), (5.0, 0.0), 1.0), &capsule((-5.0, 1.5), (5.0, 1.5), 1.0), v(0.0, 1.0), 0.5);
+        assert_apart(&capsule((-5.0, 0.0), (5.0, 0.0), 1.0), &capsule((-5.0, 2.5), (5.0, 2.5), 1.0));
+        // crossing, it takes half the length plus both radii to get them apart
+        let contact = capsule((-5.0, 0.0), (5.0, 0.0), 1.0)
+            .contact(&capsule((0.0, -5.0), (0.0, 5.0), 1.0))
+            .unwrap();
+        assert!((contact.depth - 7.0).abs() < 1e-3);
+        // end to end
+        assert_contact(&capsule((0.0, 0.0), (5.0, 0.0), 1.0), &capsule((6.0, 0.0), (10.0, 0.0), 1.0), v(1.0, 0.0), 1.0);
+    }
+
+    #[test]
+    fn polygons_against_everything_else() {
+        assert_contact(&triangle(), &capsule((6.0, 6.0), (12.0, 12.0), 2.0), diagonal(), 2.0 - 2.0f32.sqrt());
+        assert_apart(&triangle(), &capsule((6.0, 6.0), (12.0, 12.0), 1.0));
+        let square = Collider::Polygon(vec![v(4.0, 4.0), v(8.0, 4.0), v(8.0, 8.0), v(4.0, 8.0)]);
+        assert_contact(&triangle(), &square, diagonal(), 2.0f32.sqrt());
+        assert_contact(&aabb(-1.0, -0.5, 1.0, 1.5), &triangle(), v(1.0, 0.0), 1.0);
+        let turned = Collider::Obb {
+            center: v(-1.0, 0.5),
+            half_extents: v(1.0, 2.0),
+            rotation: 90.0,
+        };
+        assert_contact(&turned, &triangle(), v(1.0, 0.0), 1.0);
+        assert_apart(&triangle(), &aabb(6.0, 6.0, 8.0, 8.0));
+    }
+
+    #[test]
+    fn empty_polygons_never_touch() {
+        let empty = Collider::Polygon(Vec::new());
+        assert_apart(&empty, &circle(0.0, 0.0, 5.0));
+        assert_apart(&empty, &capsule((0.0, 0.0), (1.0, 0.0), 5.0));
+        assert_apart(&empty, &triangle());
+        assert_apart(&empty, &empty);
+    }
+
+    #[test]
+    fn grid_pairs_colliders_sharing_a_cell_once() {
+        let mut grid = SpatialGrid::new(32.0);
+        grid.insert(1, &square(10.0, 10.0));
+        grid.insert(2, &square(20.0, 20.0));
+        // far away
+        grid.insert(3, &square(500.0, 500.0));
+        // across a cell border, so shares several cells with 4
+        grid.insert(4, &square(32.0, 32.0));
+        grid.insert(5, &square(40.0, 40.0));
+        assert_eq!(grid.pairs(), vec![(1, 2), (1, 4), (2, 4), (4, 5)]);
+    }
+
+    #[test]
+    fn grid_skips_pairs_that_dont_interact() {
+        let (player, enemy, bullet) = (1, 2, 4);
+        let mut grid = SpatialGrid::new(32.0);
+        grid.insert(1, &on_layers(10.0, 10.0, bullet, enemy));
+        grid.insert(2, &on_layers(12.0, 10.0, bullet, enemy));
+        grid.insert(3, &on_layers(14.0, 10.0, enemy, bullet | player));
+        assert_eq!(grid.pairs(), vec![(1, 3), (2, 3)]);
+        assert_eq!(grid.query(&on_layers(10.0, 10.0, player, enemy)), vec![3]);
+        assert_eq!(grid.query(&on_layers(10.0, 10.0, player, bullet)), Vec::<i32>::new());
+    }
+
+    #[test]
+    fn grid_query_finds_neighbours_only() {
+        let mut grid = SpatialGrid::new(32.0);
+        grid.insert(1, &square(10.0, 10.0));
+        grid.insert(2, &square(100.0, 10.0));
+        // big enough to be in the queried cells and more
+        grid.insert(3, &Collision2D::new(v(200.0, 20.0), &at(100.0, 10.0)));
+        assert_eq!(grid.query(&square(12.0, 12.0)), vec![1, 3]);
+        assert_eq!(grid.query(&square(-300.0, 0.0)), Vec::<i32>::new());
+    }
+
+    #[test]
+    fn grid_clear_drops_the_cells() {
+        let mut grid = SpatialGrid::new(32.0);
+        for i in 0..100 {
+            grid.insert(i, &square(i as f32 * 40.0, 0.0));
+        }
+        grid.clear();
+        assert!(grid.cells.is_empty());
+        grid.insert(1, &square(10.0, 10.0));
+        assert_eq!(grid.query(&square(10.0, 10.0)), vec![1]);
     }
 }
@@ -0,0 +1,30 @@
+use crate::engine::error::{EngineError, Result};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+// The files the game is tuned with (controls, weapons, bullet patterns) are all RON, these
+// read and write them with errors that say which file was wrong.
+
+pub fn load_ron<T: DeserializeOwned>(path: &str) -> Result<T> {
+    let text = std::fs::read_to_string(path).map_err(|source| EngineError::Io {
+        path: path.to_string(),
+        source,
+    })?;
+    ron::from_str(&text).map_err(|err| config_error(path, err))
+}
+
+pub fn save_ron<T: Serialize>(path: &str, value: &T) -> Result<()> {
+    let text = ron::ser::to_string_pretty(value, ron::ser::PrettyConfig::default()).map_err(|err| config_error(path, err))?;
+    std::fs::write(path, text).map_err(|source| EngineError::Io {
+        path: path.to_string(),
+        source,
+    })
+}
+
+// For files that parse but don't make sense, e.g. a weapon without levels.
+pub fn config_error(path: &str, message: impl ToString) -> EngineError {
+    EngineError::Config {
+        path: path.to_string(),
+        message: message.to_string(),
+    }
+}
@@ -0,0 +1,61 @@
+use crate::engine::assets::AssetManager;
+use crate::engine::camera::Camera2D;
+use crate::engine::canvas::{Letterbox, ScalingMode};
+use crate::engine::gpu::Gpu;
+use crate::engine::input::Input;
+use cgmath::Vector2;
+
+// What the engine hands to game code each frame: the gpu to create entities with, the
+// assets loaded so far, the camera the frame is drawn with and the virtual size of the
+// area being drawn to, which stays the same however the window is resized.
+pub struct Context {
+    pub gpu: Gpu,
+    pub assets: AssetManager,
+    pub camera: Camera2D,
+    // empty until the game sets its bindings, usually in Game::init
+    pub input: Input,
+    pub width: u32,
+    pub height: u32,
+    letterbox: Letterbox,
+    exit_requested: bool,
+}
+
+impl Context {
+    pub fn new(gpu: Gpu, width: u32, height: u32, scaling: ScalingMode) -> Self {
+        Context {
+            gpu,
+            assets: AssetManager::new(),
+            camera: Camera2D::new(width, height),
+            input: Input::default(),
+            width,
+            height,
+            letterbox: Letterbox::new(width, height, scaling),
+            exit_requested: false,
+        }
+    }
+
+    // Ask the engine to close the window at the end of the current frame.
+    pub fn exit(&mut self) {
+        self.exit_requested = true;
+    }
+
+    pub fn should_exit(&self) -> bool {
+        self.exit_requested
+    }
+
+    // Called by the engine when the window changes size, the virtual size doesn't.
+    pub fn resize(&mut self, window_width: u32, window_height: u32) {
+        self.letterbox.resize(window_width, window_height);
+    }
+
+    pub fn letterbox(&self) -> &Letterbox {
+        &self.letterbox
+    }
+
+    // Turns a position from a window event (cursor, touch) into the world point under it.
+    // None when it's on the letterbox bars.
+    pub fn window_to_world(&self, x: f32, y: f32) -> Option<Vector2<f32>> {
+        let canvas = self.letterbox.window_to_canvas(x, y)?;
+        Some(self.camera.screen_to_world(canvas.x, canvas.y))
+    }
+}
@@ -1,6 +1,7 @@
+use crate::engine::gpu::Gpu;
+use crate::engine::sprite_batch::SpriteBatch;
+
+// Anything that can queue itself up in a sprite batch.
 pub trait Draw {
-    fn draw(&self,
-        device: &wgpu::Device,
-        encoder: &mut wgpu::CommandEncoder,
-        view: &wgpu::TextureView) -> Result<(), wgpu::SurfaceError>;
+    fn draw(&self, gpu: &Gpu, batch: &mut SpriteBatch);
 }
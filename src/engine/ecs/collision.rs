@@ -0,0 +1,325 @@
+use crate::engine::collision_2d::{Collision2D, Contact, SpatialGrid};
+use crate::engine::context::Context;
+use crate::engine::ecs::schedule::System;
+use crate::engine::ecs::{EntityId, World};
+use crate::engine::error::Result;
+use std::collections::HashMap;
+use std::time::Duration;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CollisionKind {
+    // started touching this tick
+    Enter,
+    // still touching
+    Stay,
+    // stopped touching, or one of them is gone
+    Exit,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct CollisionEvent {
+    pub kind: CollisionKind,
+    pub first: EntityId,
+    pub second: EntityId,
+    // from first towards second, None on exit
+    pub contact: Option<Contact>,
+}
+
+impl CollisionEvent {
+    fn swapped(self) -> Self {
+        CollisionEvent {
+            first: self.second,
+            second: self.first,
+            contact: self.contact.map(|contact| Contact {
+                normal: -contact.normal,
+                ..contact
+            }),
+            ..self
+        }
+    }
+}
+
+// Resource with every event of the last collision pass, for systems that would rather
+// go through them than subscribe.
+#[derive(Default)]
+pub struct CollisionEvents(pub Vec<CollisionEvent>);
+
+type Handler = Box<dyn FnMut(&mut World, &mut Context, &CollisionEvent) -> Result<()>>;
+
+struct Subscriber {
+    first: u32,
+    second: u32,
+    handler: Handler,
+}
+
+// System that finds which colliders touch and tells whoever subscribed to their layers.
+// Goes after the engine's collision system so the colliders are where their entities are.
+pub struct CollisionPass {
+    grid: SpatialGrid<EntityId>,
+    // pairs touching last tick with their layers, to tell enter from stay and for exits
+    touching: HashMap<(EntityId, EntityId), (u32, u32)>,
+    subscribers: Vec<Subscriber>,
+}
+
+impl CollisionPass {
+    // See SpatialGrid::new() for the cell size.
+    pub fn new(cell_size: f32) -> Self {
+        CollisionPass {
+            grid: SpatialGrid::new(cell_size),
+            touching: HashMap::new(),
+            subscribers: Vec::new(),
+        }
+    }
+
+    // The handler gets events between a collider on any of the first layers and one on any
+    // of the second layers, in that order. Entities it despawns get no more events this
+    // tick except exits.
+    pub fn subscribe(
+        &mut self,
+        first: u32,
+        second: u32,
+        handler: impl FnMut(&mut World, &mut Context, &CollisionEvent) -> Result<()> + 'static,
+    ) {
+        self.subscribers.push(Subscriber {
+            first,
+            second,
+            handler: Box::new(handler),
+        });
+    }
+
+    fn detect(&mut self, world: &World) -> Vec<(CollisionEvent, (u32, u32))> {
+        let colliders = world.borrow::<Collision2D>();
+        self.grid.clear();
+        for (id, collider) in colliders.iter() {
+            if collider.layer() != 0 {
+                self.grid.insert(id, collider);
+            }
+        }
+        let mut events = Vec::new();
+        let mut touching = HashMap::new();
+        for (a, b) in self.grid.pairs() {
+            let (collider_a, collider_b) = match (colliders.get(a), colliders.get(b)) {
+                (Some(collider_a), Some(collider_b)) => (collider_a, collider_b),
+                _ => continue,
+            };
+            let contact = match collider_a.contact(collider_b) {
+                Some(contact) => contact,
+                None => continue,
+            };
+            let layers = (collider_a.layer(), collider_b.layer());
+            let kind = match self.touching.contains_key(&(a, b)) {
+                true => CollisionKind::Stay,
+                false => CollisionKind::Enter,
+            };
+            touching.insert((a, b), layers);
+            let event = CollisionEvent {
+                kind,
+                first: a,
+                second: b,
+                contact: Some(contact),
+            };
+            events.push((event, layers));
+        }
+        for (&(a, b), &layers) in &self.touching {
+            if !touching.contains_key(&(a, b)) {
+                let event = CollisionEvent {
+                    kind: CollisionKind::Exit,
+                    first: a,
+                    second: b,
+                    contact: None,
+                };
+                events.push((event, layers));
+            }
+        }
+        self.touching = touching;
+        events
+    }
+}
+
+impl System for CollisionPass {
+    fn run(&mut self, world: &mut World, ctx: &mut Context, _dt: Duration) -> Result<()> {
+        let events = self.detect(world);
+        for &(event, (layer_a, layer_b)) in &events {
+            for subscriber in &mut self.subscribers {
+                let event = if layer_a & subscriber.first != 0 && layer_b & subscriber.second != 0 {
+                    event
+                } else if layer_b & subscriber.first != 0 && layer_a & subscriber.second != 0 {
+                    event.swapped()
+                } else {
+                    continue;
+                };
+                let gone = !world.is_alive(event.first) || !world.is_alive(event.second);
+                if gone && event.kind != CollisionKind::Exit {
+                    continue;
+                }
+                (subscriber.handler)(world, ctx, &event)?;
+            }
+        }
+        world.insert_resource(CollisionEvents(events.into_iter().map(|(event, _)| event).collect()));
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::canvas::ScalingMode;
+    use crate::engine::headless;
+    use crate::engine::transformation::Affine2;
+    use cgmath::{InnerSpace, Vector2};
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    const RED: u32 = 1 << 0;
+    const BLUE: u32 = 1 << 1;
+
+    type Log = Rc<RefCell<Vec<CollisionEvent>>>;
+
+    fn context() -> Option<Context> {
+        let gpu = headless::for_tests(4, 4)?.gpu;
+        Some(Context::new(gpu, 64, 64, ScalingMode::Fit))
+    }
+
+    fn at(x: f32) -> Affine2 {
+        Affine2 {
+            translation: Vector2 { x, y: 0.0 },
+            ..Affine2::identity()
+        }
+    }
+
+    // A 10x10 box at x, on the one layer and hitting the other.
+    fn spawn(world: &mut World, x: f32, layer: u32, mask: u32) -> EntityId {
+        let mut collider = Collision2D::new(Vector2 { x: 10.0, y: 10.0 }, &at(x));
+        collider.set_layers(layer, mask);
+        let id = world.spawn();
+        world.insert(id, collider);
+        id
+    }
+
+    fn move_to(world: &World, id: EntityId, x: f32) {
+        world.borrow_mut::<Collision2D>().get_mut(id).unwrap().update(&at(x));
+    }
+
+    fn logger(log: &Log) -> impl FnMut(&mut World, &mut Context, &CollisionEvent) -> Result<()> {
+        let log = log.clone();
+        move |_, _, event| {
+            log.borrow_mut().push(*event);
+            Ok(())
+        }
+    }
+
+    // Runs the pass once and hands back what the log got.
+    fn tick(pass: &mut CollisionPass, world: &mut World, ctx: &mut Context, log: &Log) -> Vec<(CollisionKind, EntityId, EntityId)> {
+        pass.run(world, ctx, Duration::ZERO).unwrap();
+        log.borrow_mut()
+            .drain(..)
+            .map(|event| (event.kind, event.first, event.second))
+            .collect()
+    }
+
+    #[test]
+    fn touching_goes_enter_stay_exit() {
+        let mut ctx = match context() {
+            Some(ctx) => ctx,
+            None => return,
+        };
+        let mut world = World::new();
+        let red = spawn(&mut world, 0.0, RED, BLUE);
+        let blue = spawn(&mut world, 100.0, BLUE, RED);
+        let log = Log::default();
+        let mut pass = CollisionPass::new(32.0);
+        pass.subscribe(RED, BLUE, logger(&log));
+
+        assert_eq!(tick(&mut pass, &mut world, &mut ctx, &log), []);
+        move_to(&world, blue, 8.0);
+        pass.run(&mut world, &mut ctx, Duration::ZERO).unwrap();
+        let events: Vec<_> = log.borrow_mut().drain(..).collect();
+        assert_eq!(events.len(), 1);
+        assert_eq!((events[0].kind, events[0].first, events[0].second), (CollisionKind::Enter, red, blue));
+        // from red towards blue, overlapping by 2
+        let contact = events[0].contact.unwrap();
+        assert!((contact.normal - Vector2 { x: 1.0, y: 0.0 }).magnitude() < 1e-4, "{:?}", contact);
+        assert!((contact.depth - 2.0).abs() < 1e-4, "{:?}", contact);
+
+        assert_eq!(tick(&mut pass, &mut world, &mut ctx, &log), [(CollisionKind::Stay, red, blue)]);
+        assert_eq!(tick(&mut pass, &mut world, &mut ctx, &log), [(CollisionKind::Stay, red, blue)]);
+        move_to(&world, blue, 100.0);
+        assert_eq!(tick(&mut pass, &mut world, &mut ctx, &log), [(CollisionKind::Exit, red, blue)]);
+        assert!(log.borrow().is_empty());
+        assert_eq!(tick(&mut pass, &mut world, &mut ctx, &log), []);
+        // and the resource has the same events
+        move_to(&world, blue, 8.0);
+        tick(&mut pass, &mut world, &mut ctx, &log);
+        let events = world.resource::<CollisionEvents>().unwrap();
+        assert_eq!(events.0.len(), 1);
+        assert_eq!(events.0[0].kind, CollisionKind::Enter);
+    }
+
+    #[test]
+    fn reversed_subscriptions_get_their_own_layer_first() {
+        let mut ctx = match context() {
+            Some(ctx) => ctx,
+            None => return,
+        };
+        let mut world = World::new();
+        // red has the smaller id, so the grid finds the pair as (red, blue)
+        let red = spawn(&mut world, 0.0, RED, BLUE);
+        let blue = spawn(&mut world, 8.0, BLUE, RED);
+        let log = Log::default();
+        let mut pass = CollisionPass::new(32.0);
+        pass.subscribe(BLUE, RED, logger(&log));
+        pass.run(&mut world, &mut ctx, Duration::ZERO).unwrap();
+        let events: Vec<_> = log.borrow_mut().drain(..).collect();
+        assert_eq!(events.len(), 1);
+        assert_eq!((events[0].first, events[0].second), (blue, red));
+        // the normal turns round with them
+        let normal = events[0].contact.unwrap().normal;
+        assert!((normal - Vector2 { x: -1.0, y: 0.0 }).magnitude() < 1e-4, "{:?}", normal);
+        // but the resource keeps the order they were found in
+        let found = world.resource::<CollisionEvents>().unwrap().0[0];
+        assert_eq!((found.first, found.second), (red, blue));
+    }
+
+    #[test]
+    fn layers_nobody_subscribed_to_are_quiet() {
+        let mut ctx = match context() {
+            Some(ctx) => ctx,
+            None => return,
+        };
+        let mut world = World::new();
+        spawn(&mut world, 0.0, RED, RED);
+        spawn(&mut world, 8.0, RED, RED);
+        let log = Log::default();
+        let mut pass = CollisionPass::new(32.0);
+        pass.subscribe(RED, BLUE, logger(&log));
+        assert_eq!(tick(&mut pass, &mut world, &mut ctx, &log), []);
+        assert_eq!(world.resource::<CollisionEvents>().unwrap().0.len(), 1);
+    }
+
+    #[test]
+    fn despawned_entities_only_get_exit() {
+        let mut ctx = match context() {
+            Some(ctx) => ctx,
+            None => return,
+        };
+        let mut world = World::new();
+        let red = spawn(&mut world, 0.0, RED, BLUE);
+        let blue = spawn(&mut world, 8.0, BLUE, RED);
+        let log = Log::default();
+        let mut pass = CollisionPass::new(32.0);
+        // the first subscriber shoots blue down as soon as they touch
+        pass.subscribe(RED, BLUE, |world, _, event| {
+            if event.kind == CollisionKind::Enter {
+                world.despawn(event.second);
+            }
+            Ok(())
+        });
+        pass.subscribe(BLUE, RED, logger(&log));
+
+        // blue is gone before the second subscriber hears about the enter
+        assert_eq!(tick(&mut pass, &mut world, &mut ctx, &log), []);
+        assert!(!world.is_alive(blue));
+        assert_eq!(tick(&mut pass, &mut world, &mut ctx, &log), [(CollisionKind::Exit, blue, red)]);
+        assert_eq!(tick(&mut pass, &mut world, &mut ctx, &log), []);
+    }
+}
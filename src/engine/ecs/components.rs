@@ -0,0 +1,31 @@
+use crate::engine::ecs::EntityId;
+use cgmath::Vector2;
+use std::time::Duration;
+
+// The engine's own components. An entity that's drawn has an engine::entity::Entity for its
+// sprite and transform, and a Collision2D if it can be hit.
+
+// Makes the entity's transform relative to another one, systems::hierarchy keeps it up to
+// date. Despawning the parent despawns the child too.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Parent(pub EntityId);
+
+// Despawned once it runs out, see systems::lifetime.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Lifetime {
+    pub remaining: Duration,
+}
+
+// Despawned as soon as its position leaves the area, e.g. bullets off the top of the screen.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct DespawnOutside {
+    pub min: Vector2<f32>,
+    pub max: Vector2<f32>,
+}
+
+// Kept inside the area after moving, e.g. the player at the edges of the screen.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ClampToBounds {
+    pub min: Vector2<f32>,
+    pub max: Vector2<f32>,
+}
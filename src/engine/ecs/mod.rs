@@ -0,0 +1,320 @@
+pub mod collision;
+pub mod components;
+pub mod schedule;
+pub mod storage;
+pub mod systems;
+
+use crate::engine::collision_2d::Collision2D;
+use crate::engine::ecs::components::{ClampToBounds, DespawnOutside, Lifetime, Parent};
+use crate::engine::ecs::storage::{AnyStorage, Storage};
+use crate::engine::entity::Entity;
+use std::any::{Any, TypeId};
+use std::cell::{Ref, RefCell, RefMut};
+use std::collections::HashMap;
+
+// Refers to an entity of a World. Ids of despawned entities stay invalid even after their
+// index is reused, the generation tells them apart.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct EntityId {
+    index: u32,
+    generation: u32,
+}
+
+// Entities are just ids, everything about them lives in components, one storage per
+// component type. Storages and resources sit behind RefCells so a system can read one
+// component type while writing another, borrowing the same one mutably twice panics.
+pub struct World {
+    generations: Vec<u32>,
+    alive: Vec<bool>,
+    free: Vec<u32>,
+    storages: HashMap<TypeId, Box<dyn AnyStorage>>,
+    resources: HashMap<TypeId, Box<dyn Any>>,
+    // despawns asked for while storages were borrowed, see queue_despawn()
+    pending_despawns: RefCell<Vec<EntityId>>,
+}
+
+impl World {
+    // The engine's components are registered up front so its systems can always borrow them.
+    pub fn new() -> Self {
+        let mut world = World {
+            generations: Vec::new(),
+            alive: Vec::new(),
+            free: Vec::new(),
+            storages: HashMap::new(),
+            resources: HashMap::new(),
+            pending_despawns: RefCell::new(Vec::new()),
+        };
+        world.register::<Entity>();
+        world.register::<Collision2D>();
+        world.register::<Parent>();
+        world.register::<Lifetime>();
+        world.register::<DespawnOutside>();
+        world.register::<ClampToBounds>();
+        world
+    }
+
+    pub fn spawn(&mut self) -> EntityId {
+        match self.free.pop() {
+            Some(index) => {
+                self.alive[index as usize] = true;
+                EntityId {
+                    index,
+                    generation: self.generations[index as usize],
+                }
+            }
+            None => {
+                self.generations.push(0);
+                self.alive.push(true);
+                EntityId {
+                    index: self.generations.len() as u32 - 1,
+                    generation: 0,
+                }
+            }
+        }
+    }
+
+    pub fn is_alive(&self, id: EntityId) -> bool {
+        let index = id.index as usize;
+        index < self.alive.len() && self.alive[index] && self.generations[index] == id.generation
+    }
+
+    // Removes the entity with all its components, and every entity parented to it.
+    // Returns how many entities went.
+    pub fn despawn(&mut self, id: EntityId) -> usize {
+        let mut removed = 0;
+        let mut pending = vec![id];
+        while let Some(id) = pending.pop() {
+            if !self.is_alive(id) {
+                continue;
+            }
+            if let Some(parents) = self.try_borrow::<Parent>() {
+                pending.extend(parents.iter().filter(|(_, parent)| parent.0 == id).map(|(child, _)| child));
+            }
+            for storage in self.storages.values() {
+                storage.remove_entity(id);
+            }
+            let index = id.index as usize;
+            self.alive[index] = false;
+            self.generations[index] = self.generations[index].wrapping_add(1);
+            self.free.push(id.index);
+            removed += 1;
+        }
+        removed
+    }
+
+    // Despawns the entity at the next flush_despawns(), for systems that are still
+    // holding borrows of the storages.
+    pub fn queue_despawn(&self, id: EntityId) {
+        self.pending_despawns.borrow_mut().push(id);
+    }
+
+    pub fn flush_despawns(&mut self) -> usize {
+        let pending = self.pending_despawns.take();
+        pending.into_iter().map(|id| self.despawn(id)).sum()
+    }
+
+    pub fn entity_count(&self) -> usize {
+        self.alive.len() - self.free.len()
+    }
+
+    // Adds the component to the entity, replacing one of the same type it already has.
+    // Does nothing for dead entities.
+    pub fn insert<T: 'static>(&mut self, id: EntityId, component: T) {
+        if !self.is_alive(id) {
+            return;
+        }
+        self.register::<T>();
+        self.borrow_mut::<T>().insert(id, component);
+    }
+
+    pub fn remove<T: 'static>(&mut self, id: EntityId) -> Option<T> {
+        self.try_borrow_mut::<T>()?.remove(id)
+    }
+
+    pub fn has<T: 'static>(&self, id: EntityId) -> bool {
+        self.storages
+            .get(&TypeId::of::<T>())
+            .is_some_and(|storage| storage.contains_entity(id))
+    }
+
+    // Makes sure there's a storage for T, so it can be borrowed before anything has one.
+    pub fn register<T: 'static>(&mut self) {
+        self.storages
+            .entry(TypeId::of::<T>())
+            .or_insert_with(|| Box::new(RefCell::new(Storage::<T>::new())));
+    }
+
+    fn cell<T: 'static>(&self) -> Option<&RefCell<Storage<T>>> {
+        self.storages
+            .get(&TypeId::of::<T>())
+            .and_then(|storage| storage.as_any().downcast_ref::<RefCell<Storage<T>>>())
+    }
+
+    // Every T in the world. Panics if T was never registered or inserted, like a RefCell
+    // does if it's already borrowed mutably.
+    pub fn borrow<T: 'static>(&self) -> Ref<'_, Storage<T>> {
+        self.try_borrow::<T>()
+            .unwrap_or_else(|| panic!("component {} was never registered", std::any::type_name::<T>()))
+    }
+
+    pub fn borrow_mut<T: 'static>(&self) -> RefMut<'_, Storage<T>> {
+        self.try_borrow_mut::<T>()
+            .unwrap_or_else(|| panic!("component {} was never registered", std::any::type_name::<T>()))
+    }
+
+    pub fn try_borrow<T: 'static>(&self) -> Option<Ref<'_, Storage<T>>> {
+        self.cell::<T>().map(RefCell::borrow)
+    }
+
+    pub fn try_borrow_mut<T: 'static>(&self) -> Option<RefMut<'_, Storage<T>>> {
+        self.cell::<T>().map(RefCell::borrow_mut)
+    }
+
+    // Ids of every entity that has all the components asked for, e.g.
+    // world.query::<Enemy>().with::<Collision2D>().without::<Parent>().ids()
+    pub fn query<T: 'static>(&self) -> Query<'_> {
+        Query {
+            world: self,
+            with: vec![TypeId::of::<T>()],
+            without: Vec::new(),
+        }
+    }
+
+    // Single instances of things systems share, like the sprites to spawn with.
+    pub fn insert_resource<R: 'static>(&mut self, resource: R) {
+        self.resources.insert(TypeId::of::<R>(), Box::new(RefCell::new(resource)));
+    }
+
+    pub fn remove_resource<R: 'static>(&mut self) -> Option<R> {
+        let resource = self.resources.remove(&TypeId::of::<R>())?;
+        resource.downcast::<RefCell<R>>().ok().map(|resource| resource.into_inner())
+    }
+
+    pub fn resource<R: 'static>(&self) -> Option<Ref<'_, R>> {
+        self.resource_cell::<R>().map(RefCell::borrow)
+    }
+
+    pub fn resource_mut<R: 'static>(&self) -> Option<RefMut<'_, R>> {
+        self.resource_cell::<R>().map(RefCell::borrow_mut)
+    }
+
+    fn resource_cell<R: 'static>(&self) -> Option<&RefCell<R>> {
+        self.resources.get(&TypeId::of::<R>())?.downcast_ref::<RefCell<R>>()
+    }
+}
+
+impl Default for World {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+pub struct Query<'w> {
+    world: &'w World,
+    with: Vec<TypeId>,
+    without: Vec<TypeId>,
+}
+
+impl<'w> Query<'w> {
+    pub fn with<T: 'static>(mut self) -> Self {
+        self.with.push(TypeId::of::<T>());
+        self
+    }
+
+    pub fn without<T: 'static>(mut self) -> Self {
+        self.without.push(TypeId::of::<T>());
+        self
+    }
+
+    // Collected up front, so the storages are free to borrow while going through them.
+    pub fn ids(&self) -> Vec<EntityId> {
+        let storages = &self.world.storages;
+        let mut with = Vec::new();
+        for type_id in &self.with {
+            match storages.get(type_id) {
+                Some(storage) => with.push(storage),
+                // nothing has this component
+                None => return Vec::new(),
+            }
+        }
+        let without: Vec<_> = self.without.iter().filter_map(|type_id| storages.get(type_id)).collect();
+        with[0]
+            .entity_ids()
+            .into_iter()
+            .filter(|&id| with[1..].iter().all(|storage| storage.contains_entity(id)))
+            .filter(|&id| !without.iter().any(|storage| storage.contains_entity(id)))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Health;
+    struct Armour;
+
+    #[test]
+    fn despawned_ids_stay_dead_after_reuse() {
+        let mut world = World::new();
+        let old = world.spawn();
+        world.insert(old, Health);
+        assert_eq!(world.despawn(old), 1);
+        let new = world.spawn();
+        assert_eq!(new.index, old.index);
+        assert_ne!(new, old);
+        assert!(!world.is_alive(old));
+        assert!(world.is_alive(new));
+        assert!(!world.has::<Health>(new));
+        // inserting for a dead id does nothing
+        world.insert(old, Health);
+        assert!(!world.has::<Health>(new));
+        assert_eq!(world.entity_count(), 1);
+    }
+
+    #[test]
+    fn despawning_a_parent_takes_its_children() {
+        let mut world = World::new();
+        let root = world.spawn();
+        let child = world.spawn();
+        let grandchild = world.spawn();
+        let other = world.spawn();
+        world.insert(child, Parent(root));
+        world.insert(grandchild, Parent(child));
+        assert_eq!(world.despawn(root), 3);
+        assert!(!world.is_alive(child));
+        assert!(!world.is_alive(grandchild));
+        assert!(world.is_alive(other));
+        assert!(world.borrow::<Parent>().is_empty());
+    }
+
+    #[test]
+    fn queued_despawns_wait_for_the_flush() {
+        let mut world = World::new();
+        let id = world.spawn();
+        world.insert(id, Health);
+        for (id, _) in world.borrow::<Health>().iter() {
+            world.queue_despawn(id);
+        }
+        assert!(world.is_alive(id));
+        assert_eq!(world.flush_despawns(), 1);
+        assert!(!world.is_alive(id));
+        assert_eq!(world.flush_despawns(), 0);
+    }
+
+    #[test]
+    fn queries_filter_on_components() {
+        let mut world = World::new();
+        let bare = world.spawn();
+        let healthy = world.spawn();
+        let armoured = world.spawn();
+        world.insert(bare, Armour);
+        world.insert(healthy, Health);
+        world.insert(armoured, Health);
+        world.insert(armoured, Armour);
+        assert_eq!(world.query::<Health>().ids(), vec![healthy, armoured]);
+        assert_eq!(world.query::<Health>().with::<Armour>().ids(), vec![armoured]);
+        assert_eq!(world.query::<Health>().without::<Armour>().ids(), vec![healthy]);
+        assert!(world.query::<String>().ids().is_empty());
+    }
+}
@@ -0,0 +1,144 @@
+use crate::engine::context::Context;
+use crate::engine::ecs::{systems, World};
+use crate::engine::error::Result;
+use std::time::Duration;
+
+// Where systems go in the tick, lower runs first. Anything in between works too, e.g.
+// MOVEMENT + 10 for something that has to see where things moved to.
+pub const INPUT: i32 = 0;
+pub const LOGIC: i32 = 100;
+pub const MOVEMENT: i32 = 200;
+pub const TRANSFORM: i32 = 300;
+pub const COLLISION: i32 = 400;
+pub const CLEANUP: i32 = 500;
+
+// Game logic run once per fixed tick over the whole world.
+pub trait System {
+    fn run(&mut self, world: &mut World, ctx: &mut Context, dt: Duration) -> Result<()>;
+}
+
+impl<F> System for F
+where
+    F: FnMut(&mut World, &mut Context, Duration) -> Result<()>,
+{
+    fn run(&mut self, world: &mut World, ctx: &mut Context, dt: Duration) -> Result<()> {
+        self(world, ctx, dt)
+    }
+}
+
+struct Entry {
+    name: &'static str,
+    order: i32,
+    system: Box<dyn System>,
+}
+
+// The systems of a world in the order they run. Systems with the same order run in the
+// order they were added.
+pub struct Schedule {
+    entries: Vec<Entry>,
+}
+
+impl Schedule {
+    pub fn new() -> Self {
+        Schedule { entries: Vec::new() }
+    }
+
+    // Movement, animation, hierarchy, collision and lifetime, which most games want.
+    pub fn with_engine_systems() -> Self {
+        let mut schedule = Self::new();
+        schedule.add("movement", MOVEMENT, systems::movement);
+        schedule.add("animation", MOVEMENT, systems::animation);
+        schedule.add("hierarchy", TRANSFORM, systems::hierarchy);
+        schedule.add("collision", COLLISION, systems::collision);
+        schedule.add("lifetime", CLEANUP, systems::lifetime);
+        schedule
+    }
+
+    pub fn add(&mut self, name: &'static str, order: i32, system: impl System + 'static) {
+        let at = self.entries.partition_point(|entry| entry.order <= order);
+        self.entries.insert(
+            at,
+            Entry {
+                name,
+                order,
+                system: Box::new(system),
+            },
+        );
+    }
+
+    // Returns true if there was a system with that name.
+    pub fn remove(&mut self, name: &str) -> bool {
+        let len = self.entries.len();
+        self.entries.retain(|entry| entry.name != name);
+        self.entries.len() != len
+    }
+
+    pub fn names(&self) -> impl Iterator<Item = &'static str> + '_ {
+        self.entries.iter().map(|entry| entry.name)
+    }
+
+    // Runs every system once. Despawns queued by a system happen before the next one runs,
+    // so nothing sees half removed entities.
+    pub fn run(&mut self, world: &mut World, ctx: &mut Context, dt: Duration) -> Result<()> {
+        for entry in &mut self.entries {
+            entry.system.run(world, ctx, dt)?;
+            world.flush_despawns();
+        }
+        Ok(())
+    }
+}
+
+impl Default for Schedule {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::canvas::ScalingMode;
+    use crate::engine::headless;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    #[test]
+    fn systems_are_sorted_by_order_then_added_order() {
+        let mut schedule = Schedule::new();
+        schedule.add("late", CLEANUP, |_: &mut World, _: &mut Context, _| Ok(()));
+        schedule.add("first", INPUT, |_: &mut World, _: &mut Context, _| Ok(()));
+        schedule.add("logic a", LOGIC, |_: &mut World, _: &mut Context, _| Ok(()));
+        schedule.add("logic b", LOGIC, |_: &mut World, _: &mut Context, _| Ok(()));
+        schedule.add("between", LOGIC + 10, |_: &mut World, _: &mut Context, _| Ok(()));
+        assert_eq!(schedule.names().collect::<Vec<_>>(), ["first", "logic a", "logic b", "between", "late"]);
+        assert!(schedule.remove("logic a"));
+        assert!(!schedule.remove("logic a"));
+        assert_eq!(schedule.names().collect::<Vec<_>>(), ["first", "logic b", "between", "late"]);
+    }
+
+    #[test]
+    fn systems_run_in_order_and_see_despawns_flushed() {
+        let gpu = match headless::for_tests(4, 4) {
+            Some(headless) => headless.gpu,
+            None => return,
+        };
+        let mut ctx = Context::new(gpu, 64, 64, ScalingMode::Fit);
+        let mut world = World::new();
+        let id = world.spawn();
+        let ran = Rc::new(RefCell::new(Vec::new()));
+        let mut schedule = Schedule::new();
+        let log = ran.clone();
+        schedule.add("checks", LOGIC, move |world: &mut World, _: &mut Context, _| {
+            log.borrow_mut().push(("checks", world.is_alive(id)));
+            Ok(())
+        });
+        let log = ran.clone();
+        schedule.add("despawns", INPUT, move |world: &mut World, _: &mut Context, _| {
+            world.queue_despawn(id);
+            log.borrow_mut().push(("despawns", world.is_alive(id)));
+            Ok(())
+        });
+        schedule.run(&mut world, &mut ctx, Duration::from_millis(16)).unwrap();
+        assert_eq!(*ran.borrow(), [("despawns", true), ("checks", false)]);
+    }
+}
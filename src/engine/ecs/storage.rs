@@ -0,0 +1,175 @@
+use crate::engine::ecs::EntityId;
+use std::any::Any;
+use std::cell::RefCell;
+
+// Every component of one type, kept packed so systems run over them without gaps.
+// sparse maps an entity's index to where its component is in dense.
+pub struct Storage<T> {
+    dense: Vec<T>,
+    owners: Vec<EntityId>,
+    sparse: Vec<Option<usize>>,
+}
+
+impl<T> Storage<T> {
+    pub fn new() -> Self {
+        Storage {
+            dense: Vec::new(),
+            owners: Vec::new(),
+            sparse: Vec::new(),
+        }
+    }
+
+    fn slot(&self, id: EntityId) -> Option<usize> {
+        let slot = (*self.sparse.get(id.index as usize)?)?;
+        // an older entity that had the same index doesn't count
+        (self.owners[slot] == id).then_some(slot)
+    }
+
+    // Replaces the component if the entity already has one.
+    pub fn insert(&mut self, id: EntityId, component: T) {
+        if let Some(slot) = self.slot(id) {
+            self.dense[slot] = component;
+            return;
+        }
+        let index = id.index as usize;
+        if index >= self.sparse.len() {
+            self.sparse.resize(index + 1, None);
+        }
+        // a stale component left by a dead entity with this index goes first
+        if let Some(slot) = self.sparse[index] {
+            let stale = self.owners[slot];
+            self.remove(stale);
+        }
+        self.sparse[index] = Some(self.dense.len());
+        self.dense.push(component);
+        self.owners.push(id);
+    }
+
+    pub fn remove(&mut self, id: EntityId) -> Option<T> {
+        let slot = self.slot(id)?;
+        self.sparse[id.index as usize] = None;
+        let component = self.dense.swap_remove(slot);
+        self.owners.swap_remove(slot);
+        // the last component now lives in the removed one's slot
+        if slot < self.owners.len() {
+            self.sparse[self.owners[slot].index as usize] = Some(slot);
+        }
+        Some(component)
+    }
+
+    pub fn get(&self, id: EntityId) -> Option<&T> {
+        self.slot(id).map(|slot| &self.dense[slot])
+    }
+
+    pub fn get_mut(&mut self, id: EntityId) -> Option<&mut T> {
+        self.slot(id).map(|slot| &mut self.dense[slot])
+    }
+
+    pub fn contains(&self, id: EntityId) -> bool {
+        self.slot(id).is_some()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (EntityId, &T)> {
+        self.owners.iter().copied().zip(self.dense.iter())
+    }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = (EntityId, &mut T)> {
+        self.owners.iter().copied().zip(self.dense.iter_mut())
+    }
+
+    pub fn ids(&self) -> &[EntityId] {
+        &self.owners
+    }
+
+    pub fn len(&self) -> usize {
+        self.dense.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.dense.is_empty()
+    }
+}
+
+impl<T> Default for Storage<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// What the world needs from a storage without knowing its component type.
+pub(crate) trait AnyStorage {
+    fn remove_entity(&self, id: EntityId);
+    fn contains_entity(&self, id: EntityId) -> bool;
+    fn entity_ids(&self) -> Vec<EntityId>;
+    fn as_any(&self) -> &dyn Any;
+}
+
+impl<T: 'static> AnyStorage for RefCell<Storage<T>> {
+    fn remove_entity(&self, id: EntityId) {
+        self.borrow_mut().remove(id);
+    }
+
+    fn contains_entity(&self, id: EntityId) -> bool {
+        self.borrow().contains(id)
+    }
+
+    fn entity_ids(&self) -> Vec<EntityId> {
+        self.borrow().ids().to_vec()
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::ecs::World;
+
+    #[test]
+    fn removing_from_the_middle_keeps_the_rest() {
+        let mut world = World::new();
+        let ids: Vec<_> = (0..4).map(|_| world.spawn()).collect();
+        let mut storage = Storage::new();
+        for (i, &id) in ids.iter().enumerate() {
+            storage.insert(id, i);
+        }
+        assert_eq!(storage.remove(ids[1]), Some(1));
+        assert_eq!(storage.len(), 3);
+        assert_eq!(storage.get(ids[1]), None);
+        // the last one was moved into the gap and can still be found
+        assert_eq!(storage.get(ids[0]), Some(&0));
+        assert_eq!(storage.get(ids[2]), Some(&2));
+        assert_eq!(storage.get(ids[3]), Some(&3));
+        assert_eq!(storage.remove(ids[1]), None);
+    }
+
+    #[test]
+    fn inserting_twice_replaces() {
+        let mut world = World::new();
+        let id = world.spawn();
+        let mut storage = Storage::new();
+        storage.insert(id, "first");
+        storage.insert(id, "second");
+        assert_eq!(storage.len(), 1);
+        assert_eq!(storage.get(id), Some(&"second"));
+    }
+
+    #[test]
+    fn stale_components_belong_to_nobody() {
+        let mut world = World::new();
+        let old = world.spawn();
+        let mut storage = Storage::new();
+        storage.insert(old, 1);
+        world.despawn(old);
+        // same index, newer generation
+        let new = world.spawn();
+        assert_eq!(storage.get(new), None);
+        assert!(!storage.contains(new));
+        storage.insert(new, 2);
+        assert_eq!(storage.len(), 1);
+        assert_eq!(storage.get(new), Some(&2));
+        assert_eq!(storage.get(old), None);
+    }
+}
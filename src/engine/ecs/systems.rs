@@ -0,0 +1,118 @@
+use crate::engine::collision_2d::Collision2D;
+use crate::engine::context::Context;
+use crate::engine::draw::Draw;
+use crate::engine::ecs::components::{ClampToBounds, DespawnOutside, Lifetime, Parent};
+use crate::engine::ecs::{EntityId, World};
+use crate::engine::entity::Entity;
+use crate::engine::error::Result;
+use crate::engine::gpu::Gpu;
+use crate::engine::sprite_batch::SpriteBatch;
+use crate::engine::transformation::Affine2;
+use std::time::Duration;
+
+// Moves every entity by its velocity, then keeps the clamped ones inside their bounds.
+pub fn movement(world: &mut World, _ctx: &mut Context, dt: Duration) -> Result<()> {
+    let mut entities = world.borrow_mut::<Entity>();
+    for (_, entity) in entities.iter_mut() {
+        entity.integrate(dt);
+    }
+    for (id, bounds) in world.borrow::<ClampToBounds>().iter() {
+        if let Some(entity) = entities.get_mut(id) {
+            entity.position.x = entity.position.x.clamp(bounds.min.x, bounds.max.x);
+            entity.position.y = entity.position.y.clamp(bounds.min.y, bounds.max.y);
+        }
+    }
+    Ok(())
+}
+
+pub fn animation(world: &mut World, _ctx: &mut Context, dt: Duration) -> Result<()> {
+    for (_, entity) in world.borrow_mut::<Entity>().iter_mut() {
+        entity.animate(dt);
+    }
+    Ok(())
+}
+
+// Hands every parent's world transform down to its children, so collision sees where
+// children really are. Runs after anything that moves things.
+pub fn hierarchy(world: &mut World, _ctx: &mut Context, _dt: Duration) -> Result<()> {
+    propagate(world, Entity::matrix, Entity::set_parent_matrix);
+    Ok(())
+}
+
+// Fits every collider around its entity as it is after this tick's moves.
+pub fn collision(world: &mut World, _ctx: &mut Context, _dt: Duration) -> Result<()> {
+    let entities = world.borrow::<Entity>();
+    for (id, collider) in world.borrow_mut::<Collision2D>().iter_mut() {
+        if let Some(entity) = entities.get(id) {
+            collider.set_size(entity.size(), &entity.matrix());
+        }
+    }
+    Ok(())
+}
+
+// Despawns entities whose Lifetime ran out or that left their DespawnOutside area.
+pub fn lifetime(world: &mut World, _ctx: &mut Context, dt: Duration) -> Result<()> {
+    for (id, lifetime) in world.borrow_mut::<Lifetime>().iter_mut() {
+        lifetime.remaining = lifetime.remaining.saturating_sub(dt);
+        if lifetime.remaining.is_zero() {
+            world.queue_despawn(id);
+        }
+    }
+    let entities = world.borrow::<Entity>();
+    for (id, area) in world.borrow::<DespawnOutside>().iter() {
+        if let Some(entity) = entities.get(id) {
+            let position = entity.matrix().translation;
+            if position.x < area.min.x || position.x > area.max.x || position.y < area.min.y || position.y > area.max.y {
+                world.queue_despawn(id);
+            }
+        }
+    }
+    Ok(())
+}
+
+// Not part of the schedule, call once per frame like Entity::interpolate.
+pub fn interpolate(world: &mut World, alpha: f32) {
+    for (_, entity) in world.borrow_mut::<Entity>().iter_mut() {
+        entity.interpolate(alpha);
+    }
+    propagate(world, Entity::render_matrix, Entity::set_parent_render_matrix);
+}
+
+pub fn render(world: &World, gpu: &Gpu, batch: &mut SpriteBatch) {
+    for (_, entity) in world.borrow::<Entity>().iter() {
+        entity.draw(gpu, batch);
+    }
+}
+
+fn propagate(world: &World, world_matrix: impl Fn(&Entity) -> Affine2, set_parent: impl Fn(&mut Entity, Affine2)) {
+    let mut entities = world.borrow_mut::<Entity>();
+    for (child, parent) in parents_first(world) {
+        let matrix = match entities.get(parent) {
+            Some(parent) => world_matrix(parent),
+            None => continue,
+        };
+        if let Some(child) = entities.get_mut(child) {
+            set_parent(child, matrix);
+        }
+    }
+}
+
+// Every (child, parent) pair, sorted so a parent is always done before its children.
+fn parents_first(world: &World) -> Vec<(EntityId, EntityId)> {
+    let parents = world.borrow::<Parent>();
+    let mut pairs: Vec<_> = parents
+        .iter()
+        .map(|(child, parent)| {
+            let mut depth = 0;
+            let mut ancestor = Some(parent.0);
+            // anything deeper than there are parents has looped back on itself
+            while let (Some(id), true) = (ancestor, depth <= parents.len()) {
+                depth += 1;
+                ancestor = parents.get(id).map(|parent| parent.0);
+            }
+            (depth, child, parent.0)
+        })
+        .collect();
+    pairs.sort_by_key(|&(depth, ..)| depth);
+    pairs.into_iter().map(|(_, child, parent)| (child, parent)).collect()
+}
@@ -1,148 +1,252 @@
-use crate::engine::render_init::RenderInit;
-use crate::engine::sprite::Sprite;
-use crate::engine::transformation::Transformation;
-use crate::engine::vertex::Vertex;
+use crate::engine::animation::Animator;
+use crate::engine::draw::Draw;
+use crate::engine::gpu::Gpu;
+use crate::engine::assets::SpriteHandle;
+use crate::engine::sprite_batch::SpriteBatch;
+use crate::engine::transformation::{Affine2, Transformation};
 use cgmath::prelude::*;
-use cgmath::Basis2;
 use cgmath::Vector2;
-use wgpu::util::DeviceExt;
-use wgpu::Queue;
-use wgpu::Surface;
-use wgpu::SurfaceConfiguration;
-use wgpu::SurfaceTexture;
-use wgpu::TextureView;
+use std::time::Duration;
 
 #[repr(C)]
 #[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
 pub struct EntityRaw {
-    pub position: [f32; 2],
-    origin: [f32; 2],
-    rotation: [[f32; 2]; 2],
-    scale: [[f32; 2]; 2],
+    // columns of the entity's Affine2
+    pub transform: [[f32; 2]; 3],
+    pub origin: [f32; 2],
+    // size of the sprite in pixels, the shared quad is scaled up to it
+    pub size: [f32; 2],
+    // part of the texture to show, see Sprite::uv_rect
+    pub uv_rect: [f32; 4],
+    // see SpriteEffects, flip is 1.0 for flipped and 0.0 for not
+    pub tint: [f32; 4],
+    pub flash: f32,
+    pub flip: [f32; 2],
+}
+
+// How an entity's sprite is coloured and mirrored when drawn, without needing another texture.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SpriteEffects {
+    // multiplied with the texture, the last component is opacity
+    pub tint: [f32; 4],
+    // 0 is the texture as it is, 1 is solid white, for hit feedback
+    pub flash: f32,
+    pub flip_x: bool,
+    pub flip_y: bool,
+}
+
+impl SpriteEffects {
+    pub fn set_opacity(&mut self, opacity: f32) {
+        self.tint[3] = opacity;
+    }
+
+    pub fn opacity(&self) -> f32 {
+        self.tint[3]
+    }
+
+    pub(crate) fn flip(&self) -> [f32; 2] {
+        [self.flip_x as u8 as f32, self.flip_y as u8 as f32]
+    }
+}
+
+impl Default for SpriteEffects {
+    fn default() -> Self {
+        SpriteEffects {
+            tint: [1.0; 4],
+            flash: 0.0,
+            flip_x: false,
+            flip_y: false,
+        }
+    }
 }
 
 // contain sprite, This struct is for rare entities, ie not sharing a sprite.
 // a shared entity with support for instancing will be created in that case.
 // move position into transformation struct
 pub struct Entity {
-    pub sprite: Sprite,
+    pub sprite: SpriteHandle,
     pub position: Vector2<f32>,
+    // pixels per second, see integrate()
+    pub velocity: Vector2<f32>,
+    pub effects: SpriteEffects,
+    // position at the previous simulation tick and the one actually drawn, see interpolate()
+    previous_position: Vector2<f32>,
+    render_position: Vector2<f32>,
+    // rotation, scale and skew, the translation is always the position
     transformation: Transformation,
-    render_pipeline: wgpu::RenderPipeline,
-    rotation_deg: f32,
-    ent_scale: f32,
+    // world transform of the parent this tick and as drawn, identity without one.
+    // Everything above is relative to it, see ecs::components::Parent.
+    parent_matrix: Affine2,
+    parent_render_matrix: Affine2,
+    layer: i32,
+    // when set, the current frame is drawn instead of the whole sprite
+    animator: Option<Animator>,
 }
 
 impl Entity {
-    pub fn new(
-        filepath: &str,
-        x: u32,
-        y: u32,
-        rotation: f32,
-        scale: f32,
-        surface: &Surface,
-        config: &SurfaceConfiguration,
-        adapter: &wgpu::Adapter,
-        queue: &wgpu::Queue,
-        device: &wgpu::Device,
-    ) -> Entity {
-        let rotation_deg = rotation;
-        let x = x as f32;
-        let y = y as f32;
-        let ent_scale = scale;
-        let position = Vector2 { x, y };
+    pub fn new(sprite: SpriteHandle, position: Vector2<f32>, rotation: f32, scale: f32) -> Entity {
         let transformation = Transformation::new(rotation, scale);
-        let render_init = pollster::block_on(RenderInit::new(config, device));
-        let render_pipeline = render_init.render_pipeline;
-        let texture_bind_group_layout = render_init.texture_bind_group_layout;
-        let sprite = Sprite::new(filepath, &texture_bind_group_layout, &device, &queue);
         Entity {
             sprite,
             position,
+            velocity: Vector2 { x: 0.0, y: 0.0 },
+            effects: SpriteEffects::default(),
+            previous_position: position,
+            render_position: position,
             transformation,
-            render_pipeline,
-            rotation_deg,
-            ent_scale,
+            parent_matrix: Affine2::identity(),
+            parent_render_matrix: Affine2::identity(),
+            layer: 0,
+            animator: None,
         }
     }
 
     // update function to be called for entities, more complicated structures such as players or enemies will call this
     // on their entities to update their position, I will also possibly implement scaling.
-    pub fn update(&mut self, x: u32, y: u32, rotation: f32, scale: f32) {
-        self.rotation_deg = rotation;
-        self.ent_scale = scale;
-        self.position.x = x as f32;
-        self.position.y = y as f32;
+    pub fn update(&mut self, position: Vector2<f32>, rotation: f32, scale: f32) {
+        self.previous_position = self.position;
+        self.position = position;
         self.transformation.update(rotation, scale);
     }
 
+    // Moves by velocity for one tick, use instead of update() for things that just fly along.
+    pub fn integrate(&mut self, dt: Duration) {
+        self.previous_position = self.position;
+        self.position += self.velocity * dt.as_secs_f32();
+    }
+
+    // Blend between the last two simulated positions, alpha comes from the fixed timestep.
+    // Without this movement stutters whenever the frame rate and tick rate don't line up.
+    pub fn interpolate(&mut self, alpha: f32) {
+        self.render_position = self.previous_position.lerp(self.position, alpha);
+    }
+
+    // Moves the animator on, if there is one. Called once per tick like update().
+    pub fn animate(&mut self, dt: Duration) {
+        if let Some(animator) = &mut self.animator {
+            animator.update(dt);
+        }
+    }
+
+    // Where the entity is in the world this tick, for collision. Drawing uses the
+    // interpolated position.
+    pub fn matrix(&self) -> Affine2 {
+        self.parent_matrix * self.local_matrix()
+    }
+
+    // Relative to the parent, the same as matrix() for entities without one.
+    pub fn local_matrix(&self) -> Affine2 {
+        Transformation {
+            translation: self.position,
+            ..self.transformation
+        }
+        .matrix()
+    }
+
+    // World transform as drawn this frame, see interpolate().
+    pub fn render_matrix(&self) -> Affine2 {
+        let local = Transformation {
+            translation: self.render_position,
+            ..self.transformation
+        };
+        self.parent_render_matrix * local.matrix()
+    }
+
+    // Set by ecs::systems::hierarchy whenever the parent moves.
+    pub fn set_parent_matrix(&mut self, matrix: Affine2) {
+        self.parent_matrix = matrix;
+    }
+
+    pub fn set_parent_render_matrix(&mut self, matrix: Affine2) {
+        self.parent_render_matrix = matrix;
+    }
+
+    // Size in pixels of what's drawn before transforming, the current frame if animated.
+    pub fn size(&self) -> Vector2<f32> {
+        match &self.animator {
+            Some(animator) => animator.frame().size,
+            None => self.sprite.size,
+        }
+    }
+
     // needed for sending to the shaders (rotation and position)
     pub fn to_raw(&self) -> EntityRaw {
+        let uv_rect = match &self.animator {
+            Some(animator) => animator.frame().uv_rect,
+            None => self.sprite.uv_rect,
+        };
         EntityRaw {
-            position: self.position.into(),
+            transform: self.render_matrix().to_cols(),
             origin: self.sprite.origin.into(),
-            // convert degrees to radians
-            rotation: self.transformation.rotation(),
-            scale: self.transformation.scale(),
+            size: self.size().into(),
+            uv_rect,
+            tint: self.effects.tint,
+            flash: self.effects.flash,
+            flip: self.effects.flip(),
         }
     }
 
-    pub fn render(
-        &self,
-        device: &wgpu::Device,
-        encoder: &mut wgpu::CommandEncoder,
-        view: &wgpu::TextureView,
-    ) -> Result<(), wgpu::SurfaceError> {
-        let entity_data = vec![self.to_raw()];
-        let entity_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-            label: Some("Instance Buffer"),
-            contents: bytemuck::cast_slice(&entity_data),
-            usage: wgpu::BufferUsages::VERTEX,
-        });
-        {
-            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
-                label: Some("Render Pass"),
-                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-                    view: &view,
-                    resolve_target: None,
-                    ops: wgpu::Operations {
-                        load: wgpu::LoadOp::Load,
-                        store: true,
-                    },
-                })],
-                depth_stencil_attachment: None,
-            });
-            // implement instancing...try and fixc the life time issue when using values in a render function
-            render_pass.set_pipeline(&self.render_pipeline);
-            render_pass.set_bind_group(0, &self.sprite.diffuse_bind_group, &[]);
-            render_pass.set_vertex_buffer(0, self.sprite.vertex_buffer.slice(..));
-            render_pass.set_vertex_buffer(1, entity_buffer.slice(..));
-            render_pass.set_index_buffer(
-                self.sprite.index_buffer.slice(..),
-                wgpu::IndexFormat::Uint16,
-            );
-            // println!("size: {:?}", self.window.inner_size());
-            render_pass.draw_indexed(0..6, 0, 0..1);
-        }
+    pub fn rotation(&self) -> f32 {
+        self.transformation.rotation
+    }
+    pub fn set_rotation(&mut self, rotation: f32) {
+        self.transformation.rotation = rotation;
+    }
+    pub fn scale(&self) -> Vector2<f32> {
+        self.transformation.scale
+    }
+    pub fn set_scale(&mut self, scale: Vector2<f32>) {
+        self.transformation.scale = scale;
+    }
+    pub fn skew(&self) -> Vector2<f32> {
+        self.transformation.skew
+    }
+    pub fn set_skew(&mut self, skew: Vector2<f32>) {
+        self.transformation.skew = skew;
+    }
 
-        Ok(())
+    // Entities on lower layers are drawn first, see SpriteBatch.
+    pub fn set_layer(&mut self, layer: i32) {
+        self.layer = layer;
+    }
+    pub fn layer(&self) -> i32 {
+        self.layer
     }
 
-    pub fn position_x(&self) -> u32 {
-        self.position.x as u32
+    // The animator's sheet has to be cut from this entity's sprite.
+    pub fn set_animator(&mut self, animator: Option<Animator>) {
+        self.animator = animator;
     }
-    pub fn position_y(&self) -> u32 {
-        self.position.y as u32
+    pub fn animator(&self) -> Option<&Animator> {
+        self.animator.as_ref()
     }
-    pub fn rotation(&self) -> f32 {
-        self.rotation_deg
+    pub fn animator_mut(&mut self) -> Option<&mut Animator> {
+        self.animator.as_mut()
     }
-    pub fn scale(&self) -> f32 {
-        self.ent_scale
+}
+
+impl Draw for Entity {
+    fn draw(&self, _gpu: &Gpu, batch: &mut SpriteBatch) {
+        batch.draw(&self.sprite, self.to_raw(), self.layer);
     }
 }
 
 impl EntityRaw {
+    const ATTRIBS: [wgpu::VertexAttribute; 9] = wgpu::vertex_attr_array![
+        // transform
+        5 => Float32x2,
+        6 => Float32x2,
+        7 => Float32x2,
+        // origin, size, uv_rect
+        8 => Float32x2,
+        9 => Float32x2,
+        10 => Float32x4,
+        // tint, flash, flip
+        11 => Float32x4,
+        12 => Float32,
+        13 => Float32x2,
+    ];
+
     pub fn desc<'a>() -> wgpu::VertexBufferLayout<'a> {
         use std::mem;
         wgpu::VertexBufferLayout {
@@ -151,38 +255,7 @@ impl EntityRaw {
             // This means that our shaders will only change to use the next
             // instance when the shader starts processing a new instance
             step_mode: wgpu::VertexStepMode::Instance,
-            attributes: &[
-                wgpu::VertexAttribute {
-                    offset: 0,
-                    shader_location: 5,
-                    format: wgpu::VertexFormat::Float32x2,
-                },
-                wgpu::VertexAttribute {
-                    offset: mem::size_of::<[f32; 2]>() as wgpu::BufferAddress,
-                    shader_location: 6,
-                    format: wgpu::VertexFormat::Float32x2,
-                },
-                wgpu::VertexAttribute {
-                    offset: mem::size_of::<[f32; 4]>() as wgpu::BufferAddress,
-                    shader_location: 7,
-                    format: wgpu::VertexFormat::Float32x2,
-                },
-                wgpu::VertexAttribute {
-                    offset: mem::size_of::<[f32; 6]>() as wgpu::BufferAddress,
-                    shader_location: 8,
-                    format: wgpu::VertexFormat::Float32x2,
-                },
-                wgpu::VertexAttribute {
-                    offset: mem::size_of::<[f32; 8]>() as wgpu::BufferAddress,
-                    shader_location: 9,
-                    format: wgpu::VertexFormat::Float32x2,
-                },
-                wgpu::VertexAttribute {
-                    offset: mem::size_of::<[f32; 10]>() as wgpu::BufferAddress,
-                    shader_location: 10,
-                    format: wgpu::VertexFormat::Float32x2,
-                },
-            ],
+            attributes: &Self::ATTRIBS,
         }
     }
 }
@@ -1,211 +1,319 @@
 // Used for entities with the same texture that will be spawed many times.
-use crate::engine::render_init::RenderInit;
+use crate::engine::animation::Animator;
+use crate::engine::draw::Draw;
+use crate::engine::entity::{EntityRaw, SpriteEffects};
+use crate::engine::error::EngineError;
+use crate::engine::gpu::Gpu;
+use crate::engine::assets::SpriteHandle;
 use crate::engine::sprite::Sprite;
-use crate::engine::transformation::Transformation;
+use crate::engine::sprite_batch::SpriteBatch;
+use crate::engine::transformation::{Affine2, Transformation};
+use cgmath::prelude::*;
 use cgmath::Vector2;
+use std::cell::Cell;
 use std::collections::HashMap;
-use wgpu::util::DeviceExt;
-use wgpu::Surface;
-use wgpu::SurfaceConfiguration;
-
-
-#[repr(C)]
-#[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
-struct InstanceRaw {
-    pub position: [f32; 2],
-    origin: [f32; 2],
-    rotation: [[f32; 2]; 2],
-    scale: [[f32; 2]; 2],
-}
+use std::rc::Rc;
+use std::time::Duration;
 
 pub struct Instance {
     position: Vector2<f32>,
+    // pixels per second, see integrate()
+    pub velocity: Vector2<f32>,
+    pub effects: SpriteEffects,
+    previous_position: Vector2<f32>,
+    render_position: Vector2<f32>,
+    // rotation, scale and skew, the translation is always the position
     transformation: Transformation,
-    rotation_deg: f32,
-    ent_scale: f32,
     origin: Vector2<f32>,
+    size: Vector2<f32>,
+    uv_rect: [f32; 4],
+    animator: Option<Animator>,
 }
 
 pub struct EntityGroup {
-    sprite: Sprite,
-    render_pipeline: wgpu::RenderPipeline,
-    instances: HashMap<u32, Instance>,
-    indices: Vec<u16>,
-    index_buffer: wgpu::Buffer,
+    sprite: SpriteHandle,
+    // Instances are kept packed in the same order as the gpu buffer, removing one moves the
+    // last instance into its slot.
+    instances: Vec<Instance>,
+    ids: Vec<u32>,
+    slots: HashMap<u32, usize>,
+    instance_buffer: Rc<wgpu::Buffer>,
+    capacity: usize,
+    layer: i32,
+    // Slots changed since the last upload, as a start..end range.
+    dirty: Cell<Option<(usize, usize)>>,
 }
 
+// Starting size of the instance buffer, it doubles whenever it runs out of room.
+const INITIAL_CAPACITY: usize = 64;
+
 // Use default positions and scale as will be empty by default so it won't matter.
 // then create add / remove function for entities.
 // It may be better to use this for singular entities as well...
 // when this is fully working..consider removing the other entity struct...
 impl EntityGroup {
-    pub fn new(
-        filepath: &str,
-        surface: &Surface,
-        config: &SurfaceConfiguration,
-        adapter: &wgpu::Adapter,
-        queue: &wgpu::Queue,
-        device: &wgpu::Device,
-    ) -> Self {
-        let render_init = pollster::block_on(RenderInit::new(config, device));
-        let render_pipeline = render_init.render_pipeline;
-        let texture_bind_group_layout = render_init.texture_bind_group_layout;
-        let sprite = Sprite::new(filepath, &texture_bind_group_layout, &device, &queue);
-        let instances: HashMap<u32, Instance> = HashMap::new();
-        let indices: Vec<u16> = Vec::new();
-        let index_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-            label: Some("Index Buffer"),
-            contents: bytemuck::cast_slice(&indices),
-            usage: wgpu::BufferUsages::INDEX,
-        });
+    pub fn new(sprite: SpriteHandle, gpu: &Gpu) -> Self {
+        let instance_buffer = EntityGroup::create_instance_buffer(&gpu.device, INITIAL_CAPACITY);
         EntityGroup {
             sprite,
-            render_pipeline,
-            instances,
-            indices,
-            index_buffer,
+            instances: Vec::new(),
+            ids: Vec::new(),
+            slots: HashMap::new(),
+            instance_buffer,
+            capacity: INITIAL_CAPACITY,
+            layer: 0,
+            dirty: Cell::new(None),
         }
     }
 
-    pub fn render(
-        &self,
-        device: &wgpu::Device,
-        encoder: &mut wgpu::CommandEncoder,
-        view: &wgpu::TextureView,
-    ) -> Result<(), wgpu::SurfaceError> {
-        if self.instances.len() == 0 {
-            return Ok(());
-        }
-        let mut instance_data: Vec<InstanceRaw> = Vec::new();
-        for instance in &self.instances {
-            instance_data.push(instance.1.to_raw());
-        }
-        let entity_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+    fn create_instance_buffer(device: &wgpu::Device, capacity: usize) -> Rc<wgpu::Buffer> {
+        Rc::new(device.create_buffer(&wgpu::BufferDescriptor {
             label: Some("Instance Buffer"),
-            contents: bytemuck::cast_slice(&instance_data),
-            usage: wgpu::BufferUsages::VERTEX,
-        });
-        {
-            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
-                label: Some("Render Pass"),
-                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-                    view: &view,
-                    resolve_target: None,
-                    ops: wgpu::Operations {
-                        load: wgpu::LoadOp::Load,
-                        store: true,
-                    },
-                })],
-                depth_stencil_attachment: None,
-            });
-            // implement instancing...try and fixc the life time issue when using values in a render function
-            render_pass.set_pipeline(&self.render_pipeline);
-            render_pass.set_bind_group(0, &self.sprite.diffuse_bind_group, &[]);
-            render_pass.set_vertex_buffer(0, self.sprite.vertex_buffer.slice(..));
-            render_pass.set_vertex_buffer(1, entity_buffer.slice(..));
-            render_pass.set_index_buffer(
-                self.index_buffer.slice(..),
-                wgpu::IndexFormat::Uint16,
-            );
-            // println!("size: {:?}", self.window.inner_size());
-            render_pass.draw_indexed(0..(self.instances.len() * 6) as _, 0, 0..self.instances.len() as _);
-        }
+            size: (capacity * std::mem::size_of::<EntityRaw>()) as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        }))
+    }
 
-        Ok(())
+    fn mark_dirty(&self, start: usize, end: usize) {
+        let range = match self.dirty.get() {
+            Some((dirty_start, dirty_end)) => (dirty_start.min(start), dirty_end.max(end)),
+            None => (start, end),
+        };
+        self.dirty.set(Some(range));
     }
 
-    pub fn remove_instance(&mut self, id: u32, device: &wgpu::Device) -> Result<(), &str> {
-        if !self.instances.contains_key(&id) {
-            return Err("Invalid id");
+    // Writes only the slots that changed since the last call into the instance buffer.
+    fn upload(&self, queue: &wgpu::Queue) {
+        let (start, end) = match self.dirty.take() {
+            Some(range) => range,
+            None => return,
+        };
+        let end = end.min(self.instances.len());
+        if start >= end {
+            return;
         }
-        self.instances.remove(&id);
-        for _ in 0..6 {
-            self.indices.pop();
+        let instance_data: Vec<EntityRaw> = self.instances[start..end].iter().map(Instance::to_raw).collect();
+        queue.write_buffer(
+            &self.instance_buffer,
+            (start * std::mem::size_of::<EntityRaw>()) as wgpu::BufferAddress,
+            bytemuck::cast_slice(&instance_data),
+        );
+    }
+
+    pub fn remove_instance(&mut self, id: u32) -> Result<(), EngineError> {
+        let slot = self.slots.remove(&id).ok_or(EngineError::InvalidEntityId(id))?;
+        self.instances.swap_remove(slot);
+        self.ids.swap_remove(slot);
+        // the last instance now lives in the removed one's slot
+        if slot < self.instances.len() {
+            self.slots.insert(self.ids[slot], slot);
+            self.mark_dirty(slot, slot + 1);
         }
-        self.index_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-            label: Some("Index Buffer"),
-            contents: bytemuck::cast_slice(&self.indices),
-            usage: wgpu::BufferUsages::INDEX,
-        });
         Ok(())
     }
+
     pub fn add_instance(
         &mut self,
         id: u32,
-        x: u32,
-        y: u32,
+        position: Vector2<f32>,
         rotation: f32,
         scale: f32,
         device: &wgpu::Device,
-    ) -> Result<(), &str> {
+    ) -> Result<(), EngineError> {
         // insert will update the value at the key if it already exists... thus this is needed
-        if self.instances.contains_key(&id) {
-            return Err("ID already exists");
+        if self.slots.contains_key(&id) {
+            return Err(EngineError::DuplicateEntityId(id));
+        }
+        let slot = self.instances.len();
+        if slot == self.capacity {
+            // Grow geometrically so adding n instances only reallocates log(n) times.
+            // The new buffer starts empty, so everything gets uploaded again.
+            self.capacity *= 2;
+            self.instance_buffer = EntityGroup::create_instance_buffer(device, self.capacity);
+            self.mark_dirty(0, slot);
         }
-        let instance = Instance::new(x, y, rotation, scale, self.sprite.origin);
-        self.instances.insert(id, instance);
-        self.indices.extend_from_slice(&[0, 1, 2, 0, 2, 3]);
-        self.index_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-            label: Some("Index Buffer"),
-            contents: bytemuck::cast_slice(&self.indices),
-            usage: wgpu::BufferUsages::INDEX,
-        });
+        let instance = Instance::new(position, rotation, scale, &self.sprite);
+        self.instances.push(instance);
+        self.ids.push(id);
+        self.slots.insert(id, slot);
+        self.mark_dirty(slot, slot + 1);
         Ok(())
     }
 
-    pub fn get_instance(&mut self, id: u32) -> Result<&mut Instance, &str> {
-        self.instances.get_mut(&id).ok_or("Invalid id")
+    // The instance is assumed to be changed and gets uploaded again on the next render.
+    pub fn get_instance(&mut self, id: u32) -> Result<&mut Instance, EngineError> {
+        let slot = *self.slots.get(&id).ok_or(EngineError::InvalidEntityId(id))?;
+        self.mark_dirty(slot, slot + 1);
+        Ok(&mut self.instances[slot])
+    }
+
+    // For looking only, nothing gets uploaded again.
+    pub fn instance(&self, id: u32) -> Result<&Instance, EngineError> {
+        let slot = *self.slots.get(&id).ok_or(EngineError::InvalidEntityId(id))?;
+        Ok(&self.instances[slot])
+    }
+
+    pub fn interpolate(&mut self, alpha: f32) {
+        let mut changed: Option<(usize, usize)> = None;
+        for (slot, instance) in self.instances.iter_mut().enumerate() {
+            if instance.interpolate(alpha) {
+                changed = Some(match changed {
+                    Some((start, _)) => (start, slot + 1),
+                    None => (slot, slot + 1),
+                });
+            }
+        }
+        if let Some((start, end)) = changed {
+            self.mark_dirty(start, end);
+        }
+    }
+
+    // Moves every instance by its velocity for one tick.
+    pub fn integrate(&mut self, dt: Duration) {
+        for instance in &mut self.instances {
+            instance.integrate(dt);
+        }
+    }
+
+    // Moves every instance's animator on, only instances whose frame changed get uploaded.
+    pub fn animate(&mut self, dt: Duration) {
+        for slot in 0..self.instances.len() {
+            if self.instances[slot].animate(dt) {
+                self.mark_dirty(slot, slot + 1);
+            }
+        }
     }
 
     pub fn count(&self) -> usize {
         self.instances.len()
     }
+
+    pub fn set_layer(&mut self, layer: i32) {
+        self.layer = layer;
+    }
+    pub fn layer(&self) -> i32 {
+        self.layer
+    }
+}
+
+impl Draw for EntityGroup {
+    // The whole group is one draw out of its own buffer, only what changed gets uploaded.
+    fn draw(&self, gpu: &Gpu, batch: &mut SpriteBatch) {
+        self.upload(&gpu.queue);
+        batch.draw_instances(&self.sprite, self.instance_buffer.clone(), self.instances.len() as u32, self.layer);
+    }
 }
 
 impl Instance {
-    fn new(x: u32, y: u32, rotation: f32, scale: f32, origin: Vector2<f32>) -> Self {
-        let position = Vector2 {
-            x: x as f32,
-            y: y as f32,
-        };
+    fn new(position: Vector2<f32>, rotation: f32, scale: f32, sprite: &Sprite) -> Self {
         let transformation = Transformation::new(rotation, scale);
         Instance {
             position,
+            velocity: Vector2 { x: 0.0, y: 0.0 },
+            effects: SpriteEffects::default(),
+            previous_position: position,
+            render_position: position,
             transformation,
-            rotation_deg: rotation,
-            ent_scale: scale,
-            origin,
+            origin: sprite.origin,
+            size: sprite.size,
+            uv_rect: sprite.uv_rect,
+            animator: None,
         }
     }
 
-    pub fn update(&mut self, x: u32, y: u32, rotation: f32, scale: f32) {
-        self.rotation_deg = rotation;
-        self.ent_scale = scale;
-        self.position.x = x as f32;
-        self.position.y = y as f32;
+    pub fn update(&mut self, position: Vector2<f32>, rotation: f32, scale: f32) {
+        self.previous_position = self.position;
+        self.position = position;
         self.transformation.update(rotation, scale);
     }
 
-    fn to_raw(&self) -> InstanceRaw {
-        InstanceRaw {
-            position: self.position.into(),
-            origin: self.origin.into(),
-            // convert degrees to radians
-            rotation: self.transformation.rotation(),
-            scale: self.transformation.scale(),
+    // Moves by velocity for one tick, see Entity::integrate.
+    pub fn integrate(&mut self, dt: Duration) {
+        self.previous_position = self.position;
+        self.position += self.velocity * dt.as_secs_f32();
+    }
+
+    // Returns whether the drawn position actually moved.
+    pub fn interpolate(&mut self, alpha: f32) -> bool {
+        let render_position = self.previous_position.lerp(self.position, alpha);
+        let moved = render_position != self.render_position;
+        self.render_position = render_position;
+        moved
+    }
+
+    // Returns whether the frame shown changed.
+    pub fn animate(&mut self, dt: Duration) -> bool {
+        match &mut self.animator {
+            Some(animator) => animator.update(dt),
+            None => false,
         }
     }
 
-    pub fn position_x(&self) -> u32 {
-        self.position.x as u32
+    // The animator's sheet has to be cut from the group's sprite.
+    pub fn set_animator(&mut self, animator: Option<Animator>) {
+        self.animator = animator;
+    }
+    pub fn animator(&self) -> Option<&Animator> {
+        self.animator.as_ref()
+    }
+
+    fn to_raw(&self) -> EntityRaw {
+        let uv_rect = match &self.animator {
+            Some(animator) => animator.frame().uv_rect,
+            None => self.uv_rect,
+        };
+        let transform = Transformation {
+            translation: self.render_position,
+            ..self.transformation
+        };
+        EntityRaw {
+            transform: transform.matrix().to_cols(),
+            origin: self.origin.into(),
+            size: self.size().into(),
+            uv_rect,
+            tint: self.effects.tint,
+            flash: self.effects.flash,
+            flip: self.effects.flip(),
+        }
     }
-    pub fn position_y(&self) -> u32 {
-        self.position.y as u32
+
+    pub fn position(&self) -> Vector2<f32> {
+        self.position
     }
     pub fn rotation(&self) -> f32 {
-        self.rotation_deg
+        self.transformation.rotation
+    }
+    pub fn set_rotation(&mut self, rotation: f32) {
+        self.transformation.rotation = rotation;
+    }
+    pub fn scale(&self) -> Vector2<f32> {
+        self.transformation.scale
+    }
+    pub fn set_scale(&mut self, scale: Vector2<f32>) {
+        self.transformation.scale = scale;
     }
-    pub fn scale(&self) -> f32 {
-        self.ent_scale
+    pub fn skew(&self) -> Vector2<f32> {
+        self.transformation.skew
+    }
+    pub fn set_skew(&mut self, skew: Vector2<f32>) {
+        self.transformation.skew = skew;
+    }
+
+    // See Entity::matrix.
+    pub fn matrix(&self) -> Affine2 {
+        Transformation {
+            translation: self.position,
+            ..self.transformation
+        }
+        .matrix()
+    }
+
+    // See Entity::size.
+    pub fn size(&self) -> Vector2<f32> {
+        match &self.animator {
+            Some(animator) => animator.frame().size,
+            None => self.size,
+        }
     }
 }
@@ -0,0 +1,111 @@
+use std::fmt;
+
+// Everything the engine can fail at without it being a bug, so callers get a message
+// instead of a panic when e.g. a png is missing.
+#[derive(Debug)]
+pub enum EngineError {
+    // an asset file couldn't be read
+    Io { path: String, source: std::io::Error },
+    // an asset file was read but isn't a usable image
+    Decode { path: String, source: image::ImageError },
+    // a rendered frame couldn't be written out
+    Encode { path: String, source: image::ImageError },
+    // an atlas manifest couldn't be parsed, written or doesn't match its pages
+    Manifest { path: String, message: String },
+    // a config file (e.g. key bindings) couldn't be parsed or written
+    Config { path: String, message: String },
+    // an image is bigger than a whole atlas page
+    ImageTooLarge { name: String, page_size: u32 },
+    NoAdapter,
+    // the gamepad backend couldn't start
+    Gamepad(String),
+    RequestDevice(wgpu::RequestDeviceError),
+    CreateWindow(winit::error::OsError),
+    CreateSurface(wgpu::CreateSurfaceError),
+    Surface(wgpu::SurfaceError),
+    ReadBack(wgpu::BufferAsyncError),
+    InvalidEntityId(u32),
+    DuplicateEntityId(u32),
+    // a fixed timestep of zero ticks per second
+    InvalidTickRate,
+}
+
+pub type Result<T> = std::result::Result<T, EngineError>;
+
+impl fmt::Display for EngineError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EngineError::Io { path, source } => write!(f, "couldn't read {}: {}", path, source),
+            EngineError::Decode { path, source } => write!(f, "couldn't decode {}: {}", path, source),
+            EngineError::Encode { path, source } => write!(f, "couldn't write {}: {}", path, source),
+            EngineError::Manifest { path, message } => write!(f, "bad atlas manifest {}: {}", path, message),
+            EngineError::Config { path, message } => write!(f, "bad config file {}: {}", path, message),
+            EngineError::ImageTooLarge { name, page_size } => {
+                write!(f, "{} doesn't fit in a {}x{} atlas page", name, page_size, page_size)
+            }
+            EngineError::NoAdapter => write!(f, "no suitable graphics adapter found"),
+            EngineError::Gamepad(message) => write!(f, "couldn't start gamepad support: {}", message),
+            EngineError::RequestDevice(err) => write!(f, "couldn't create graphics device: {}", err),
+            EngineError::CreateWindow(err) => write!(f, "couldn't create window: {}", err),
+            EngineError::CreateSurface(err) => write!(f, "couldn't create window surface: {}", err),
+            EngineError::Surface(err) => write!(f, "surface error: {}", err),
+            EngineError::ReadBack(err) => write!(f, "couldn't read back rendered frame: {}", err),
+            EngineError::InvalidEntityId(id) => write!(f, "no entity with id {}", id),
+            EngineError::DuplicateEntityId(id) => write!(f, "an entity with id {} already exists", id),
+            EngineError::InvalidTickRate => write!(f, "the tick rate has to be at least 1 tick per second"),
+        }
+    }
+}
+
+impl std::error::Error for EngineError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            EngineError::Io { source, .. } => Some(source),
+            EngineError::Decode { source, .. } => Some(source),
+            EngineError::Encode { source, .. } => Some(source),
+            EngineError::RequestDevice(err) => Some(err),
+            EngineError::CreateWindow(err) => Some(err),
+            EngineError::CreateSurface(err) => Some(err),
+            EngineError::Surface(err) => Some(err),
+            EngineError::ReadBack(err) => Some(err),
+            EngineError::NoAdapter
+            | EngineError::Gamepad(_)
+            | EngineError::Manifest { .. }
+            | EngineError::Config { .. }
+            | EngineError::ImageTooLarge { .. }
+            | EngineError::InvalidEntityId(_)
+            | EngineError::DuplicateEntityId(_)
+            | EngineError::InvalidTickRate => None,
+        }
+    }
+}
+
+impl From<wgpu::RequestDeviceError> for EngineError {
+    fn from(err: wgpu::RequestDeviceError) -> Self {
+        EngineError::RequestDevice(err)
+    }
+}
+
+impl From<winit::error::OsError> for EngineError {
+    fn from(err: winit::error::OsError) -> Self {
+        EngineError::CreateWindow(err)
+    }
+}
+
+impl From<wgpu::CreateSurfaceError> for EngineError {
+    fn from(err: wgpu::CreateSurfaceError) -> Self {
+        EngineError::CreateSurface(err)
+    }
+}
+
+impl From<wgpu::SurfaceError> for EngineError {
+    fn from(err: wgpu::SurfaceError) -> Self {
+        EngineError::Surface(err)
+    }
+}
+
+impl From<wgpu::BufferAsyncError> for EngineError {
+    fn from(err: wgpu::BufferAsyncError) -> Self {
+        EngineError::ReadBack(err)
+    }
+}
@@ -0,0 +1,37 @@
+use crate::engine::canvas::ScalingMode;
+use crate::engine::context::Context;
+use crate::engine::error::Result;
+use crate::engine::sprite_batch::SpriteBatch;
+use std::time::Duration;
+use winit::event::WindowEvent;
+
+// Implemented by a game built on the engine, see run_game.
+// The engine owns the window, the gpu and the frame loop and calls back into the game.
+pub trait Game {
+    // Size of the playfield every gameplay coordinate is in, whatever size the window is.
+    // Frames are drawn at this size and then scaled onto the window.
+    fn virtual_size(&self) -> (u32, u32) {
+        (562, 1021)
+    }
+
+    fn scaling(&self) -> ScalingMode {
+        ScalingMode::Fit
+    }
+
+    // Called once the gpu is ready, before the first update.
+    fn init(&mut self, ctx: &mut Context) -> Result<()>;
+
+    // Called once per fixed tick, dt is always the tick length.
+    fn update(&mut self, ctx: &mut Context, dt: Duration) -> Result<()>;
+
+    // Called once per frame to queue up everything to draw, the engine flushes the batch
+    // afterwards. alpha is how far the frame is between the last tick and the next one,
+    // for interpolating positions (see Entity::interpolate).
+    fn render(&mut self, ctx: &Context, alpha: f32, batch: &mut SpriteBatch);
+
+    // Returns true if the event was used, unused events fall through to the engine
+    // (close button, resizing). Keys are usually better read through ctx.input.
+    fn input(&mut self, _ctx: &mut Context, _event: &WindowEvent) -> bool {
+        false
+    }
+}
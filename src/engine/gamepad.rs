@@ -0,0 +1,295 @@
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+
+// Buttons by where they are on the pad, so South is A on an Xbox pad and Cross on a
+// PlayStation one.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum GamepadButton {
+    South,
+    East,
+    North,
+    West,
+    LeftBumper,
+    RightBumper,
+    LeftTrigger,
+    RightTrigger,
+    Select,
+    Start,
+    LeftStick,
+    RightStick,
+    DPadUp,
+    DPadDown,
+    DPadLeft,
+    DPadRight,
+}
+
+// Stick axes go from -1 to 1, up and right are positive.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum GamepadAxis {
+    LeftStickX,
+    LeftStickY,
+    RightStickX,
+    RightStickY,
+}
+
+// Stays the same for as long as the pad is plugged in.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct GamepadId(pub usize);
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum GamepadEvent {
+    Connected(GamepadId),
+    // everything the pad was holding counts as released
+    Disconnected(GamepadId),
+    ButtonPressed(GamepadId, GamepadButton),
+    ButtonReleased(GamepadId, GamepadButton),
+    AxisMoved(GamepadId, GamepadAxis, f32),
+}
+
+// Where gamepad events come from, Input polls it once per tick.
+pub trait GamepadBackend {
+    // Everything that happened since the last poll, oldest first.
+    fn poll(&mut self) -> Vec<GamepadEvent>;
+}
+
+// A pretend gamepad that plays back events, one step per poll, for driving Input without
+// real hardware.
+#[derive(Default)]
+pub struct ScriptedGamepad {
+    steps: VecDeque<Vec<GamepadEvent>>,
+}
+
+impl ScriptedGamepad {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Events for the next poll after the ones already scripted.
+    pub fn then(mut self, events: Vec<GamepadEvent>) -> Self {
+        self.steps.push_back(events);
+        self
+    }
+
+    // Polls where nothing happens.
+    pub fn wait(mut self, polls: usize) -> Self {
+        self.steps.extend((0..polls).map(|_| Vec::new()));
+        self
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.steps.is_empty()
+    }
+}
+
+impl GamepadBackend for ScriptedGamepad {
+    fn poll(&mut self) -> Vec<GamepadEvent> {
+        self.steps.pop_front().unwrap_or_default()
+    }
+}
+
+// Real pads, needs the gamepad feature.
+#[cfg(feature = "gamepad")]
+pub struct GilrsBackend {
+    gilrs: gilrs::Gilrs,
+}
+
+#[cfg(feature = "gamepad")]
+impl GilrsBackend {
+    pub fn new() -> crate::engine::error::Result<Self> {
+        let gilrs = gilrs::Gilrs::new().map_err(|err| crate::engine::error::EngineError::Gamepad(err.to_string()))?;
+        Ok(GilrsBackend { gilrs })
+    }
+
+    fn button(button: gilrs::Button) -> Option<GamepadButton> {
+        use gilrs::Button;
+        Some(match button {
+            Button::South => GamepadButton::South,
+            Button::East => GamepadButton::East,
+            Button::North => GamepadButton::North,
+            Button::West => GamepadButton::West,
+            Button::LeftTrigger => GamepadButton::LeftBumper,
+            Button::RightTrigger => GamepadButton::RightBumper,
+            Button::LeftTrigger2 => GamepadButton::LeftTrigger,
+            Button::RightTrigger2 => GamepadButton::RightTrigger,
+            Button::Select => GamepadButton::Select,
+            Button::Start => GamepadButton::Start,
+            Button::LeftThumb => GamepadButton::LeftStick,
+            Button::RightThumb => GamepadButton::RightStick,
+            Button::DPadUp => GamepadButton::DPadUp,
+            Button::DPadDown => GamepadButton::DPadDown,
+            Button::DPadLeft => GamepadButton::DPadLeft,
+            Button::DPadRight => GamepadButton::DPadRight,
+            _ => return None,
+        })
+    }
+
+    fn axis(axis: gilrs::Axis) -> Option<GamepadAxis> {
+        use gilrs::Axis;
+        Some(match axis {
+            Axis::LeftStickX => GamepadAxis::LeftStickX,
+            Axis::LeftStickY => GamepadAxis::LeftStickY,
+            Axis::RightStickX => GamepadAxis::RightStickX,
+            Axis::RightStickY => GamepadAxis::RightStickY,
+            _ => return None,
+        })
+    }
+}
+
+#[cfg(feature = "gamepad")]
+impl GamepadBackend for GilrsBackend {
+    fn poll(&mut self) -> Vec<GamepadEvent> {
+        use gilrs::EventType;
+        let mut events = Vec::new();
+        while let Some(event) = self.gilrs.next_event() {
+            let id = GamepadId(event.id.into());
+            let event = match event.event {
+                EventType::Connected => Some(GamepadEvent::Connected(id)),
+                EventType::Disconnected => Some(GamepadEvent::Disconnected(id)),
+                EventType::ButtonPressed(button, _) => {
+                    Self::button(button).map(|button| GamepadEvent::ButtonPressed(id, button))
+                }
+                EventType::ButtonReleased(button, _) => {
+                    Self::button(button).map(|button| GamepadEvent::ButtonReleased(id, button))
+                }
+                EventType::AxisChanged(axis, value, _) => {
+                    Self::axis(axis).map(|axis| GamepadEvent::AxisMoved(id, axis, value))
+                }
+                _ => None,
+            };
+            events.extend(event);
+        }
+        events
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::input::{Binding, Direction, Input, InputMap};
+    use GamepadEvent::*;
+
+    const PAD: GamepadId = GamepadId(0);
+
+    fn input_with(script: ScriptedGamepad) -> Input {
+        let mut map = InputMap::new();
+        map.bind("fire", Binding::Button(GamepadButton::South));
+        map.bind("right", Binding::Axis(GamepadAxis::LeftStickX, Direction::Positive));
+        map.bind("left", Binding::Axis(GamepadAxis::LeftStickX, Direction::Negative));
+        let mut input = Input::new(map);
+        input.set_gamepad_backend(Box::new(script));
+        input
+    }
+
+    #[test]
+    fn buttons_are_pressed_held_and_released() {
+        let script = ScriptedGamepad::new()
+            .then(vec![Connected(PAD)])
+            .then(vec![ButtonPressed(PAD, GamepadButton::South)])
+            .wait(1)
+            .then(vec![ButtonReleased(PAD, GamepadButton::South)])
+            // a tap shorter than a tick
+            .then(vec![ButtonPressed(PAD, GamepadButton::South), ButtonReleased(PAD, GamepadButton::South)]);
+        let mut input = input_with(script);
+        input.update();
+        assert_eq!(input.gamepads().collect::<Vec<_>>(), [PAD]);
+        assert!(!input.held("fire"));
+        input.update();
+        assert!(input.pressed("fire") && input.held("fire"));
+        input.update();
+        assert!(!input.pressed("fire") && input.held("fire"));
+        input.update();
+        assert!(input.released("fire"));
+        input.update();
+        assert!(input.pressed("fire"));
+        input.update();
+        assert!(input.released("fire"));
+    }
+
+    #[test]
+    fn disconnecting_lets_go_of_everything() {
+        let script = ScriptedGamepad::new()
+            .then(vec![Connected(PAD), ButtonPressed(PAD, GamepadButton::South), AxisMoved(PAD, GamepadAxis::LeftStickX, 1.0)])
+            .then(vec![Disconnected(PAD)]);
+        let mut input = input_with(script);
+        input.update();
+        assert!(input.held("fire") && input.held("right"));
+        input.update();
+        assert!(input.released("fire") && input.released("right"));
+        assert_eq!(input.gamepads().count(), 0);
+        assert_eq!(input.axis_value(GamepadAxis::LeftStickX), 0.0);
+    }
+
+    #[test]
+    fn sticks_inside_the_deadzone_dont_count() {
+        let script = ScriptedGamepad::new()
+            .then(vec![AxisMoved(PAD, GamepadAxis::LeftStickX, 0.2)])
+            .then(vec![AxisMoved(PAD, GamepadAxis::LeftStickX, -0.625)])
+            .then(vec![AxisMoved(PAD, GamepadAxis::LeftStickX, -0.3)]);
+        let mut input = input_with(script);
+        input.update();
+        assert!(!input.held("right"));
+        assert_eq!(input.axis_value(GamepadAxis::LeftStickX), 0.0);
+        input.update();
+        assert!(input.held("left"));
+        // rescaled so just outside the deadzone is still near 0 and all the way is 1
+        assert!((input.axis_value(GamepadAxis::LeftStickX) + 0.5).abs() < 1e-5);
+        input.set_deadzone(0.5);
+        input.update();
+        assert!(input.released("left"));
+    }
+
+    #[test]
+    fn sticks_steer_as_far_as_theyre_pushed() {
+        let script = ScriptedGamepad::new()
+            .then(vec![AxisMoved(PAD, GamepadAxis::LeftStickX, 0.625)])
+            .then(vec![AxisMoved(PAD, GamepadAxis::LeftStickX, 0.1)]);
+        let mut input = input_with(script);
+        input.update();
+        assert!((input.value("right") - 0.5).abs() < 1e-5);
+        assert_eq!(input.value("left"), 0.0);
+        assert!((input.axis("left", "right") - 0.5).abs() < 1e-5);
+        input.update();
+        assert_eq!(input.axis("left", "right"), 0.0);
+    }
+
+    #[test]
+    fn sticks_have_a_round_deadzone() {
+        let script = ScriptedGamepad::new().then(vec![
+            AxisMoved(PAD, GamepadAxis::LeftStickX, 0.2),
+            AxisMoved(PAD, GamepadAxis::LeftStickY, 0.2),
+        ]);
+        let mut input = input_with(script);
+        input.update();
+        // each axis is inside the deadzone but together they're past it
+        let stick = input.stick(GamepadAxis::LeftStickX, GamepadAxis::LeftStickY);
+        assert!(stick.x > 0.0 && (stick.x - stick.y).abs() < 1e-6);
+        assert_eq!(input.axis_value(GamepadAxis::LeftStickX), 0.0);
+    }
+
+    #[test]
+    fn listening_rebinds_the_next_button() {
+        let script = ScriptedGamepad::new()
+            .then(vec![ButtonPressed(PAD, GamepadButton::East)])
+            .then(vec![ButtonReleased(PAD, GamepadButton::East)])
+            .then(vec![ButtonPressed(PAD, GamepadButton::East)]);
+        let mut input = input_with(script);
+        input.listen_for_binding("fire");
+        input.update();
+        assert!(!input.held("fire"));
+        assert_eq!(input.map().bindings("fire"), [Binding::Button(GamepadButton::East)]);
+        input.update();
+        input.update();
+        assert!(input.pressed("fire"));
+        assert!(!input.is_listening());
+    }
+
+    #[test]
+    fn scripts_run_out() {
+        let mut script = ScriptedGamepad::new().wait(2).then(vec![Connected(PAD)]);
+        assert!(script.poll().is_empty());
+        assert!(script.poll().is_empty());
+        assert_eq!(script.poll(), [Connected(PAD)]);
+        assert!(script.is_finished());
+        assert!(script.poll().is_empty());
+    }
+}
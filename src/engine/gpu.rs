@@ -0,0 +1,62 @@
+use crate::engine::error::Result;
+use crate::engine::render_cache::RenderCache;
+use std::rc::Rc;
+
+// Device, queue and the texture format everything is drawn in.
+// Shared by the windowed State and the headless renderer, so entities don't care which one
+// they end up being drawn by.
+pub struct Gpu {
+    pub adapter: wgpu::Adapter,
+    pub device: wgpu::Device,
+    pub queue: wgpu::Queue,
+    pub format: wgpu::TextureFormat,
+    pub render_cache: RenderCache,
+}
+
+impl Gpu {
+    pub async fn request_adapter(
+        instance: &wgpu::Instance,
+        compatible_surface: Option<&wgpu::Surface>,
+        force_fallback_adapter: bool,
+    ) -> Option<wgpu::Adapter> {
+        instance
+            .request_adapter(&wgpu::RequestAdapterOptions {
+                power_preference: wgpu::PowerPreference::default(),
+                compatible_surface,
+                force_fallback_adapter,
+            })
+            .await
+    }
+
+    pub async fn new(adapter: wgpu::Adapter, format: wgpu::TextureFormat) -> Result<Self> {
+        let (device, queue) = adapter
+            .request_device(
+                &wgpu::DeviceDescriptor {
+                    features: wgpu::Features::empty(),
+                    // WebGL doesn't support all of wgpu's features, so if
+                    // we're building for the web we'll have to disable some.
+                    limits: if cfg!(target_arch = "wasm32") {
+                        wgpu::Limits::downlevel_webgl2_defaults()
+                    } else {
+                        wgpu::Limits::default()
+                    },
+                    label: None,
+                },
+                None, // Trace path
+            )
+            .await?;
+        let render_cache = RenderCache::new(&device);
+        Ok(Gpu {
+            adapter,
+            device,
+            queue,
+            format,
+            render_cache,
+        })
+    }
+
+    // The sprite pipeline for the format this gpu draws in, built on first use.
+    pub fn render_pipeline(&self) -> Rc<wgpu::RenderPipeline> {
+        self.render_cache.render_pipeline(&self.device, self.format)
+    }
+}
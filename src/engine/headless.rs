@@ -0,0 +1,209 @@
+use crate::engine::camera::Camera2D;
+use crate::engine::draw::Draw;
+use crate::engine::gpu::Gpu;
+use crate::engine::sprite_batch::SpriteBatch;
+use crate::engine::error::EngineError;
+use crate::engine::error::Result;
+
+// Renders into an offscreen texture instead of a window surface, so frames can be produced
+// without a display (CI, build boxes) and read back as RGBA pixels.
+// Entities and entity groups are drawn exactly as they are in the windowed State.
+pub struct Headless {
+    pub gpu: Gpu,
+    // starts out showing exactly the target's area, move it to render some other part
+    pub camera: Camera2D,
+    batch: SpriteBatch,
+    width: u32,
+    height: u32,
+    texture: wgpu::Texture,
+    view: wgpu::TextureView,
+    output_buffer: wgpu::Buffer,
+    padded_bytes_per_row: u32,
+}
+
+impl Headless {
+    pub async fn new(width: u32, height: u32) -> Result<Self> {
+        let instance = wgpu::Instance::new(wgpu::InstanceDescriptor {
+            backends: wgpu::Backends::all(),
+            dx12_shader_compiler: Default::default(),
+        });
+        // Prefer a software adapter so output doesn't depend on the GPU of whatever machine
+        // runs it, but still work on machines that only expose a hardware one.
+        let adapter = match Gpu::request_adapter(&instance, None, true).await {
+            Some(adapter) => adapter,
+            None => Gpu::request_adapter(&instance, None, false)
+                .await
+                .ok_or(EngineError::NoAdapter)?,
+        };
+        let gpu = Gpu::new(adapter, wgpu::TextureFormat::Rgba8UnormSrgb).await?;
+
+        let texture = gpu.device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Headless Target"),
+            size: wgpu::Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: gpu.format,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+            view_formats: &[],
+        });
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+
+        // Rows copied out of a texture have to be aligned, the padding is stripped on read back.
+        let align = wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
+        let padded_bytes_per_row = (4 * width).div_ceil(align) * align;
+        let output_buffer = gpu.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Headless Output Buffer"),
+            size: (padded_bytes_per_row * height) as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+
+        let batch = SpriteBatch::new(&gpu);
+        Ok(Headless {
+            gpu,
+            camera: Camera2D::new(width, height),
+            batch,
+            width,
+            height,
+            texture,
+            view,
+            output_buffer,
+            padded_bytes_per_row,
+        })
+    }
+
+    // Clears the target, draws everything in order and reads the frame back.
+    pub fn render(&mut self, drawables: &[&dyn Draw], clear_color: wgpu::Color) -> Result<image::RgbaImage> {
+        let device = &self.gpu.device;
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Headless Encoder"),
+        });
+        for drawable in drawables {
+            drawable.draw(&self.gpu, &mut self.batch);
+        }
+        self.batch
+            .flush(&self.gpu, &mut encoder, &self.view, &self.camera, Some(clear_color));
+        encoder.copy_texture_to_buffer(
+            wgpu::ImageCopyTexture {
+                aspect: wgpu::TextureAspect::All,
+                texture: &self.texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d::ZERO,
+            },
+            wgpu::ImageCopyBuffer {
+                buffer: &self.output_buffer,
+                layout: wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: std::num::NonZeroU32::new(self.padded_bytes_per_row),
+                    rows_per_image: std::num::NonZeroU32::new(self.height),
+                },
+            },
+            wgpu::Extent3d {
+                width: self.width,
+                height: self.height,
+                depth_or_array_layers: 1,
+            },
+        );
+        self.gpu.queue.submit(std::iter::once(encoder.finish()));
+
+        let buffer_slice = self.output_buffer.slice(..);
+        let (sender, receiver) = std::sync::mpsc::channel();
+        buffer_slice.map_async(wgpu::MapMode::Read, move |result| {
+            sender.send(result).ok();
+        });
+        device.poll(wgpu::Maintain::Wait);
+        // The callback always runs during poll(Wait), so the sender can't have gone away.
+        receiver.recv().unwrap()?;
+
+        let unpadded_bytes_per_row = (4 * self.width) as usize;
+        let mut pixels = Vec::with_capacity(unpadded_bytes_per_row * self.height as usize);
+        {
+            let data = buffer_slice.get_mapped_range();
+            for row in data.chunks(self.padded_bytes_per_row as usize) {
+                pixels.extend_from_slice(&row[..unpadded_bytes_per_row]);
+            }
+        }
+        self.output_buffer.unmap();
+
+        // pixels is exactly width * height * 4 bytes, from_raw only fails if it isn't
+        Ok(image::RgbaImage::from_raw(self.width, self.height, pixels).unwrap())
+    }
+
+    pub fn save_png(&mut self, drawables: &[&dyn Draw], clear_color: wgpu::Color, path: &str) -> Result<()> {
+        let frame = self.render(drawables, clear_color)?;
+        frame
+            .save_with_format(path, image::ImageFormat::Png)
+            .map_err(|source| EngineError::Encode {
+                path: path.to_string(),
+                source,
+            })
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+}
+
+// For tests that need a gpu, None when the machine has no adapter at all so they can be
+// skipped instead of failing.
+#[cfg(test)]
+pub(crate) fn for_tests(width: u32, height: u32) -> Option<Headless> {
+    match pollster::block_on(Headless::new(width, height)) {
+        Ok(headless) => Some(headless),
+        Err(EngineError::NoAdapter) => {
+            eprintln!("no graphics adapter, skipping");
+            None
+        }
+        Err(err) => panic!("couldn't set up headless rendering: {}", err),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::assets::AssetManager;
+    use crate::engine::entity::Entity;
+    use cgmath::Vector2;
+
+    // Rerun with UPDATE_GOLDEN=1 to write it again after a change that's meant to look different.
+    const GOLDEN: &str = "tests/golden/sprites.png";
+    // per channel, adapters don't all filter and blend to exactly the same value
+    const TOLERANCE: u8 = 8;
+
+    #[test]
+    fn sprites_match_golden_image() {
+        let mut headless = match for_tests(128, 128) {
+            Some(headless) => headless,
+            None => return,
+        };
+        let mut assets = AssetManager::new();
+        let sprite = assets.sprite(&headless.gpu, "assets/enemy.png").unwrap();
+        let plain = Entity::new(sprite.clone(), Vector2 { x: 32.0, y: 96.0 }, 0.0, 1.0);
+        let rotated = Entity::new(sprite.clone(), Vector2 { x: 96.0, y: 96.0 }, 45.0, 1.0);
+        let scaled = Entity::new(sprite, Vector2 { x: 64.0, y: 32.0 }, 0.0, 2.0);
+        let frame = headless.render(&[&plain, &rotated, &scaled], wgpu::Color::BLACK).unwrap();
+
+        if std::env::var_os("UPDATE_GOLDEN").is_some() {
+            std::fs::create_dir_all("tests/golden").unwrap();
+            frame.save(GOLDEN).unwrap();
+            return;
+        }
+        let golden = image::open(GOLDEN).unwrap().to_rgba8();
+        assert_eq!(frame.dimensions(), golden.dimensions());
+        let off = frame
+            .pixels()
+            .zip(golden.pixels())
+            .filter(|(a, b)| a.0.iter().zip(b.0.iter()).any(|(a, b)| a.abs_diff(*b) > TOLERANCE))
+            .count();
+        assert_eq!(off, 0, "{} pixels differ from {}", off, GOLDEN);
+    }
+}
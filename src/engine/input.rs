@@ -0,0 +1,510 @@
+use crate::engine::config;
+use crate::engine::error::Result;
+use crate::engine::gamepad::{GamepadAxis, GamepadBackend, GamepadButton, GamepadEvent, GamepadId};
+use cgmath::prelude::*;
+use cgmath::Vector2;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use winit::event::{ElementState, KeyboardInput, VirtualKeyCode, WindowEvent};
+
+// How far a stick has to move before it counts, worn sticks don't sit exactly at 0.
+pub const DEFAULT_DEADZONE: f32 = 0.25;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Direction {
+    Negative,
+    Positive,
+}
+
+// Something physical the player can press. A stick axis counts as pressed once it's past
+// the deadzone in the given direction, any connected pad will do.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Binding {
+    Key(VirtualKeyCode),
+    Button(GamepadButton),
+    Axis(GamepadAxis, Direction),
+}
+
+impl Binding {
+    pub fn is_gamepad(&self) -> bool {
+        !matches!(self, Binding::Key(_))
+    }
+}
+
+// Which bindings trigger which action. Action names are up to the game, in a ron file it
+// looks like
+// (actions: {"up": [Key(Up), Key(W), Axis(LeftStickY, Positive)], "fire": [Key(Z), Button(South)]})
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct InputMap {
+    pub actions: BTreeMap<String, Vec<Binding>>,
+}
+
+impl InputMap {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn load(path: &str) -> Result<Self> {
+        config::load_ron(path)
+    }
+
+    pub fn save(&self, path: &str) -> Result<()> {
+        config::save_ron(path, self)
+    }
+
+    // Adds a binding to the action, on top of the ones it already has.
+    pub fn bind(&mut self, action: &str, binding: Binding) {
+        let bindings = self.actions.entry(action.to_string()).or_default();
+        if !bindings.contains(&binding) {
+            bindings.push(binding);
+        }
+    }
+
+    pub fn unbind(&mut self, action: &str, binding: Binding) {
+        if let Some(bindings) = self.actions.get_mut(action) {
+            bindings.retain(|&bound| bound != binding);
+        }
+    }
+
+    // Replaces every binding of the action.
+    pub fn rebind(&mut self, action: &str, bindings: Vec<Binding>) {
+        self.actions.insert(action.to_string(), bindings);
+    }
+
+    pub fn bindings(&self, action: &str) -> &[Binding] {
+        self.actions.get(action).map_or(&[], Vec::as_slice)
+    }
+}
+
+// Tracks which actions are held, owned by the Context. The engine feeds it window events
+// as they come in and calls update() before every tick, so pressed() and released() are
+// true for exactly one tick, even if the key went down and up again in between.
+// Gamepads come from the backend, polled at the start of update().
+pub struct Input {
+    map: InputMap,
+    // keys held down
+    down: HashSet<Binding>,
+    // pressed since the last update, so taps shorter than a tick aren't lost
+    tapped: HashSet<Binding>,
+    held: HashSet<String>,
+    previous: HashSet<String>,
+    // how far each held action is pushed, see value()
+    values: HashMap<String, f32>,
+    // action the next key or button pressed gets bound to, see listen_for_binding()
+    listening: Option<String>,
+    gamepad_backend: Option<Box<dyn GamepadBackend>>,
+    gamepads: BTreeSet<GamepadId>,
+    buttons: HashSet<(GamepadId, GamepadButton)>,
+    axes: HashMap<(GamepadId, GamepadAxis), f32>,
+    deadzone: f32,
+    // world point under the mouse or finger, see cursor()
+    cursor: Option<Vector2<f32>>,
+}
+
+impl Input {
+    pub fn new(map: InputMap) -> Self {
+        Input {
+            map,
+            down: HashSet::new(),
+            tapped: HashSet::new(),
+            held: HashSet::new(),
+            previous: HashSet::new(),
+            values: HashMap::new(),
+            listening: None,
+            gamepad_backend: None,
+            gamepads: BTreeSet::new(),
+            buttons: HashSet::new(),
+            axes: HashMap::new(),
+            deadzone: DEFAULT_DEADZONE,
+            cursor: None,
+        }
+    }
+
+    // Replaces the current backend, pads connected through the old one are dropped.
+    pub fn set_gamepad_backend(&mut self, backend: Box<dyn GamepadBackend>) {
+        self.gamepad_backend = Some(backend);
+        self.gamepads.clear();
+        self.buttons.clear();
+        self.axes.clear();
+    }
+
+    // From 0 to just under 1, how far sticks have to move before they count.
+    pub fn set_deadzone(&mut self, deadzone: f32) {
+        self.deadzone = deadzone.clamp(0.0, 0.99);
+    }
+
+    pub fn deadzone(&self) -> f32 {
+        self.deadzone
+    }
+
+    // Pads plugged in right now.
+    pub fn gamepads(&self) -> impl Iterator<Item = GamepadId> + '_ {
+        self.gamepads.iter().copied()
+    }
+
+    pub fn map(&self) -> &InputMap {
+        &self.map
+    }
+
+    // For rebinding at runtime, takes effect from the next tick.
+    pub fn map_mut(&mut self) -> &mut InputMap {
+        &mut self.map
+    }
+
+    pub fn set_map(&mut self, map: InputMap) {
+        self.map = map;
+    }
+
+    // The next key or button pressed replaces the action's bindings of the same kind (keys
+    // or gamepad) instead of doing anything else, for a "press a key" options screen.
+    pub fn listen_for_binding(&mut self, action: &str) {
+        self.listening = Some(action.to_string());
+    }
+
+    pub fn is_listening(&self) -> bool {
+        self.listening.is_some()
+    }
+
+    // Returns true if the event was used up by rebinding.
+    pub fn handle_event(&mut self, event: &WindowEvent) -> bool {
+        match event {
+            WindowEvent::KeyboardInput {
+                input: KeyboardInput {
+                    state,
+                    virtual_keycode: Some(keycode),
+                    ..
+                },
+                ..
+            } => {
+                let binding = Binding::Key(*keycode);
+                match state {
+                    ElementState::Pressed => {
+                        if self.capture(binding) {
+                            return true;
+                        }
+                        self.press(binding);
+                    }
+                    ElementState::Released => self.release(binding),
+                }
+                false
+            }
+            // keys let go of while the window isn't focused never send a release
+            WindowEvent::Focused(false) => {
+                self.down.clear();
+                false
+            }
+            _ => false,
+        }
+    }
+
+    fn capture(&mut self, binding: Binding) -> bool {
+        let action = match self.listening.take() {
+            Some(action) => action,
+            None => return false,
+        };
+        let mut bindings = self.map.bindings(&action).to_vec();
+        bindings.retain(|bound| bound.is_gamepad() != binding.is_gamepad());
+        bindings.push(binding);
+        self.map.rebind(&action, bindings);
+        true
+    }
+
+    // For keys, gamepad state comes from the backend.
+    pub fn press(&mut self, binding: Binding) {
+        self.down.insert(binding);
+        self.tapped.insert(binding);
+    }
+
+    pub fn release(&mut self, binding: Binding) {
+        self.down.remove(&binding);
+    }
+
+    pub fn handle_gamepad_event(&mut self, event: GamepadEvent) {
+        match event {
+            GamepadEvent::Connected(id) => {
+                self.gamepads.insert(id);
+            }
+            GamepadEvent::Disconnected(id) => {
+                self.gamepads.remove(&id);
+                self.buttons.retain(|&(pad, _)| pad != id);
+                self.axes.retain(|&(pad, _), _| pad != id);
+            }
+            GamepadEvent::ButtonPressed(id, button) => {
+                // some backends only say a pad is there once it's used
+                self.gamepads.insert(id);
+                let binding = Binding::Button(button);
+                if !self.capture(binding) {
+                    self.buttons.insert((id, button));
+                    self.tapped.insert(binding);
+                }
+            }
+            GamepadEvent::ButtonReleased(id, button) => {
+                self.buttons.remove(&(id, button));
+            }
+            GamepadEvent::AxisMoved(id, axis, value) => {
+                self.gamepads.insert(id);
+                self.axes.insert((id, axis), value.clamp(-1.0, 1.0));
+            }
+        }
+    }
+
+    // Called by the engine before each tick.
+    pub fn update(&mut self) {
+        if let Some(backend) = &mut self.gamepad_backend {
+            for event in backend.poll() {
+                self.handle_gamepad_event(event);
+            }
+        }
+        self.previous = std::mem::take(&mut self.held);
+        let values: HashMap<_, _> = self
+            .map
+            .actions
+            .iter()
+            .map(|(action, bindings)| {
+                let value = bindings.iter().map(|&binding| self.binding_value(binding)).fold(0.0, f32::max);
+                (action.clone(), value)
+            })
+            .filter(|&(_, value)| value > 0.0)
+            .collect();
+        self.held.extend(values.keys().cloned());
+        self.values = values;
+        self.tapped.clear();
+    }
+
+    // 0 to 1, keys and buttons are all or nothing.
+    fn binding_value(&self, binding: Binding) -> f32 {
+        if self.tapped.contains(&binding) {
+            return 1.0;
+        }
+        match binding {
+            Binding::Key(_) => self.down.contains(&binding) as u8 as f32,
+            Binding::Button(button) => self.buttons.iter().any(|&(_, held)| held == button) as u8 as f32,
+            Binding::Axis(axis, Direction::Positive) => self.axis_value(axis).max(0.0),
+            Binding::Axis(axis, Direction::Negative) => (-self.axis_value(axis)).max(0.0),
+        }
+    }
+
+    // Of whichever pad has it pushed furthest.
+    fn raw_axis(&self, axis: GamepadAxis) -> f32 {
+        self.axes
+            .iter()
+            .filter(|((_, moved), _)| *moved == axis)
+            .map(|(_, &value)| value)
+            .fold(0.0, |furthest: f32, value| if value.abs() > furthest.abs() { value } else { furthest })
+    }
+
+    // The axis of whichever pad has it pushed furthest, 0 inside the deadzone and scaled so
+    // it still goes all the way from 0 to 1 outside it.
+    pub fn axis_value(&self, axis: GamepadAxis) -> f32 {
+        let value = self.raw_axis(axis);
+        if value.abs() <= self.deadzone {
+            return 0.0;
+        }
+        value.signum() * (value.abs() - self.deadzone) / (1.0 - self.deadzone)
+    }
+
+    // Both axes of a stick as one vector, with a round deadzone so diagonals aren't
+    // harder to hit than straight lines. Never longer than 1.
+    pub fn stick(&self, x: GamepadAxis, y: GamepadAxis) -> Vector2<f32> {
+        let stick = Vector2 {
+            x: self.raw_axis(x),
+            y: self.raw_axis(y),
+        };
+        let length = stick.magnitude();
+        if length <= self.deadzone {
+            return Vector2::zero();
+        }
+        let scaled = ((length - self.deadzone) / (1.0 - self.deadzone)).min(1.0);
+        stick / length * scaled
+    }
+
+    pub fn held(&self, action: &str) -> bool {
+        self.held.contains(action)
+    }
+
+    // Went down this tick.
+    pub fn pressed(&self, action: &str) -> bool {
+        self.held.contains(action) && !self.previous.contains(action)
+    }
+
+    // Went up this tick.
+    pub fn released(&self, action: &str) -> bool {
+        !self.held.contains(action) && self.previous.contains(action)
+    }
+
+    // Where the mouse or finger was in the world when it last moved, None when it's off the
+    // canvas. Set by the engine from window events, see Context::window_to_world.
+    pub fn cursor(&self) -> Option<Vector2<f32>> {
+        self.cursor
+    }
+
+    pub fn set_cursor(&mut self, cursor: Option<Vector2<f32>>) {
+        self.cursor = cursor;
+    }
+
+    // How far the action is pushed this tick, from 0 to 1. Keys and buttons are 0 or 1,
+    // sticks go smoothly from the edge of the deadzone to all the way.
+    pub fn value(&self, action: &str) -> f32 {
+        self.values.get(action).copied().unwrap_or(0.0)
+    }
+
+    // -1 to 1 from a pair of opposite actions, e.g. left and right.
+    pub fn axis(&self, negative: &str, positive: &str) -> f32 {
+        self.value(positive) - self.value(negative)
+    }
+
+    // Two axes as one vector, e.g. for steering. Never longer than 1, so holding two
+    // arrow keys isn't faster than one.
+    pub fn direction(&self, left: &str, right: &str, down: &str, up: &str) -> Vector2<f32> {
+        let direction = Vector2 {
+            x: self.axis(left, right),
+            y: self.axis(down, up),
+        };
+        if direction.magnitude2() > 1.0 {
+            direction.normalize()
+        } else {
+            direction
+        }
+    }
+}
+
+impl Default for Input {
+    fn default() -> Self {
+        Self::new(InputMap::new())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::error::EngineError;
+
+    fn key_event(keycode: VirtualKeyCode, state: ElementState) -> WindowEvent<'static> {
+        #[allow(deprecated)]
+        WindowEvent::KeyboardInput {
+            // only ever compared, never used to talk to a real device
+            device_id: unsafe { winit::event::DeviceId::dummy() },
+            input: KeyboardInput {
+                scancode: 0,
+                state,
+                virtual_keycode: Some(keycode),
+                modifiers: Default::default(),
+            },
+            is_synthetic: false,
+        }
+    }
+
+    fn fire_map() -> InputMap {
+        let mut map = InputMap::new();
+        map.bind("fire", Binding::Key(VirtualKeyCode::Z));
+        map.bind("fire", Binding::Button(GamepadButton::South));
+        map
+    }
+
+    #[test]
+    fn maps_parse_from_ron() {
+        let map: InputMap =
+            ron::from_str(r#"(actions: {"up": [Key(Up), Key(W), Axis(LeftStickY, Positive)], "fire": [Button(South)]})"#)
+                .unwrap();
+        assert_eq!(
+            map.bindings("up"),
+            [
+                Binding::Key(VirtualKeyCode::Up),
+                Binding::Key(VirtualKeyCode::W),
+                Binding::Axis(GamepadAxis::LeftStickY, Direction::Positive)
+            ]
+        );
+        assert_eq!(map.bindings("fire"), [Binding::Button(GamepadButton::South)]);
+        assert!(map.bindings("bomb").is_empty());
+    }
+
+    #[test]
+    fn maps_save_and_load_back() {
+        let path = std::env::temp_dir().join(format!("input_map_{}.ron", std::process::id()));
+        let path = path.to_str().unwrap();
+        let map = fire_map();
+        map.save(path).unwrap();
+        assert_eq!(InputMap::load(path).unwrap(), map);
+        std::fs::write(path, "(actions: {\"fire\": [Key(NotAKey)]})").unwrap();
+        assert!(matches!(InputMap::load(path), Err(EngineError::Config { .. })));
+        std::fs::remove_file(path).ok();
+    }
+
+    #[test]
+    fn binding_and_rebinding() {
+        let mut map = fire_map();
+        // already there
+        map.bind("fire", Binding::Key(VirtualKeyCode::Z));
+        assert_eq!(map.bindings("fire").len(), 2);
+        map.unbind("fire", Binding::Key(VirtualKeyCode::Z));
+        assert_eq!(map.bindings("fire"), [Binding::Button(GamepadButton::South)]);
+        map.rebind("fire", vec![Binding::Key(VirtualKeyCode::Space)]);
+        assert_eq!(map.bindings("fire"), [Binding::Key(VirtualKeyCode::Space)]);
+    }
+
+    #[test]
+    fn keys_are_pressed_for_one_tick() {
+        let mut input = Input::new(fire_map());
+        input.handle_event(&key_event(VirtualKeyCode::Z, ElementState::Pressed));
+        input.update();
+        assert!(input.pressed("fire") && input.held("fire"));
+        input.update();
+        assert!(!input.pressed("fire") && input.held("fire"));
+        input.handle_event(&key_event(VirtualKeyCode::Z, ElementState::Released));
+        input.update();
+        assert!(input.released("fire") && !input.held("fire"));
+        // down and up again between ticks still counts
+        input.handle_event(&key_event(VirtualKeyCode::Z, ElementState::Pressed));
+        input.handle_event(&key_event(VirtualKeyCode::Z, ElementState::Released));
+        input.update();
+        assert!(input.pressed("fire"));
+        input.update();
+        assert!(input.released("fire"));
+    }
+
+    #[test]
+    fn keys_steer_at_full_length_even_diagonally() {
+        let mut map = InputMap::new();
+        for (action, key) in [("left", VirtualKeyCode::Left), ("right", VirtualKeyCode::Right), ("down", VirtualKeyCode::Down), ("up", VirtualKeyCode::Up)] {
+            map.bind(action, Binding::Key(key));
+        }
+        let mut input = Input::new(map);
+        input.press(Binding::Key(VirtualKeyCode::Right));
+        input.update();
+        assert_eq!(input.value("right"), 1.0);
+        assert_eq!(input.axis("left", "right"), 1.0);
+        assert_eq!(input.direction("left", "right", "down", "up"), Vector2::new(1.0, 0.0));
+        input.press(Binding::Key(VirtualKeyCode::Up));
+        input.update();
+        let direction = input.direction("left", "right", "down", "up");
+        assert!((direction.magnitude() - 1.0).abs() < 1e-6);
+        assert!((direction.x - direction.y).abs() < 1e-6);
+        // opposite keys cancel out
+        input.press(Binding::Key(VirtualKeyCode::Left));
+        input.update();
+        assert_eq!(input.axis("left", "right"), 0.0);
+    }
+
+    #[test]
+    fn listening_rebinds_the_next_key() {
+        let mut input = Input::new(fire_map());
+        input.listen_for_binding("fire");
+        assert!(input.handle_event(&key_event(VirtualKeyCode::X, ElementState::Pressed)));
+        assert!(!input.is_listening());
+        input.update();
+        // used up by the rebinding
+        assert!(!input.held("fire"));
+        // the key is replaced, the pad button stays
+        assert_eq!(
+            input.map().bindings("fire"),
+            [Binding::Button(GamepadButton::South), Binding::Key(VirtualKeyCode::X)]
+        );
+        input.handle_event(&key_event(VirtualKeyCode::X, ElementState::Released));
+        input.handle_event(&key_event(VirtualKeyCode::Z, ElementState::Pressed));
+        input.update();
+        assert!(!input.held("fire"));
+        input.handle_event(&key_event(VirtualKeyCode::X, ElementState::Pressed));
+        input.update();
+        assert!(input.pressed("fire"));
+    }
+}
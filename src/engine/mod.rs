@@ -1,12 +1,28 @@
+pub mod animation;
+pub mod assets;
+pub mod atlas;
+pub mod camera;
+pub mod canvas;
+pub mod config;
+pub mod ecs;
 pub mod entity;
+pub mod error;
+pub mod gamepad;
+pub mod gpu;
+pub mod headless;
+pub mod input;
 pub mod process_window_event;
-pub mod render_init;
+pub mod render_cache;
 pub mod run;
 pub mod sprite;
+pub mod sprite_batch;
 pub mod state;
+pub mod timestep;
 pub mod texture;
 pub mod transformation;
 pub mod vertex;
 pub mod entity_group;
 pub mod collision_2d;
+pub mod context;
 pub mod draw;
+pub mod game;
@@ -1,27 +1,34 @@
-use winit::event::WindowEvent;
-use winit::window::WindowId;
+use winit::event::{TouchPhase, WindowEvent};
 use winit::event_loop::ControlFlow;
-use winit::event::*;
+use crate::engine::game::Game;
 use crate::engine::state::State;
 
-pub fn window_event(
-    ref event: &WindowEvent,
-    window_id: &WindowId,
+pub fn window_event<G: Game>(
+    event: &WindowEvent,
     control_flow: &mut ControlFlow,
-    state: &mut State,
+    state: &mut State<G>,
 ) {
+    // before the game gets the event, so Input::cursor() is already where it points
+    match event {
+        WindowEvent::CursorMoved { position, .. } => {
+            let cursor = state.ctx.window_to_world(position.x as f32, position.y as f32);
+            state.ctx.input.set_cursor(cursor);
+        }
+        WindowEvent::CursorLeft { .. } => state.ctx.input.set_cursor(None),
+        WindowEvent::Touch(touch) => {
+            let cursor = match touch.phase {
+                TouchPhase::Started | TouchPhase::Moved => {
+                    state.ctx.window_to_world(touch.location.x as f32, touch.location.y as f32)
+                }
+                TouchPhase::Ended | TouchPhase::Cancelled => None,
+            };
+            state.ctx.input.set_cursor(cursor);
+        }
+        _ => {}
+    }
     if !state.input(event) {
         match event {
-            WindowEvent::CloseRequested
-            | WindowEvent::KeyboardInput {
-                input:
-                    KeyboardInput {
-                        state: ElementState::Pressed,
-                        virtual_keycode: Some(VirtualKeyCode::Escape),
-                        ..
-                    },
-                ..
-            } => *control_flow = ControlFlow::Exit,
+            WindowEvent::CloseRequested => *control_flow = ControlFlow::Exit,
             WindowEvent::Resized(physical_size) => {
                 state.resize(*physical_size);
             }
@@ -1,18 +1,17 @@
-use cgmath::prelude::*;
-#[cfg(target_arch = "wasm32")]
-use wasm_bindgen::prelude::*;
-use wgpu::util::DeviceExt;
+use crate::engine::error::Result;
+use crate::engine::game::Game;
 use crate::engine::state::State;
 use crate::engine::process_window_event::window_event;
 
 use winit::{
     event::*,
     event_loop::{ControlFlow, EventLoop},
-    window::{Window, WindowBuilder, WindowId},
+    window::WindowBuilder,
 };
 
-#[cfg_attr(target_arch = "wasm32", wasm_bindgen(start))]
-pub async fn run() {
+// Only returns if setting up the window or gpu fails, errors after that are logged and
+// close the window.
+pub async fn run<G: Game + 'static>(game: G) -> Result<()> {
     cfg_if::cfg_if! {
         if #[cfg(target_arch = "wasm32")] {
             std::panic::set_hook(Box::new(console_error_panic_hook::hook));
@@ -23,9 +22,9 @@ pub async fn run() {
     }
 
     let event_loop = EventLoop::new();
-    let window = WindowBuilder::new().build(&event_loop).unwrap();
+    let window = WindowBuilder::new().build(&event_loop)?;
 
-    let mut state = State::new(window).await;
+    let mut state = State::new(window, game).await?;
 
     #[cfg(target_arch = "wasm32")]
     {
@@ -52,30 +51,18 @@ pub async fn run() {
                 ref event,
                 window_id,
             } if window_id == state.window().id() => {
-                if !state.input(event) {
-                    match event {
-                        WindowEvent::CloseRequested
-                        | WindowEvent::KeyboardInput {
-                            input:
-                                KeyboardInput {
-                                    state: ElementState::Pressed,
-                                    virtual_keycode: Some(VirtualKeyCode::Escape),
-                                    ..
-                                },
-                            ..
-                        } => *control_flow = ControlFlow::Exit,
-                        WindowEvent::Resized(physical_size) => {
-                            state.resize(*physical_size);
-                        }
-                        WindowEvent::ScaleFactorChanged { new_inner_size, .. } => {
-                            state.resize(**new_inner_size);
-                        }
-                        _ => {}
-                    }
-                }
+                window_event(event, control_flow, &mut state);
             }
             Event::RedrawRequested(window_id) if window_id == state.window().id() => {
-                state.update();
+                if let Err(err) = state.update() {
+                    log::error!("{}", err);
+                    *control_flow = ControlFlow::Exit;
+                    return;
+                }
+                if state.finished() {
+                    *control_flow = ControlFlow::Exit;
+                    return;
+                }
                 match state.render() {
                     Ok(_) => {}
                     Err(wgpu::SurfaceError::Lost | wgpu::SurfaceError::Outdated) => state.resize(state.size),
@@ -6,52 +6,50 @@ struct VertexInput {
 struct VertexOutput {
     @builtin(position) clip_position: vec4<f32>,
     @location(0) tex_coords: vec2<f32>,
+    @location(1) tint: vec4<f32>,
+    @location(2) flash: f32,
 }
 
 struct EntityInput {
-    @location(5) position: vec2<f32>,
-    @location(6) origin: vec2<f32>,
-    @location(7) rotation_1: vec2<f32>,
-    @location(8) rotation_2: vec2<f32>,
-    @location(9) scale_1: vec2<f32>,
-    @location(10) scale_2: vec2<f32>,
+    // columns of a 3x2 affine matrix
+    @location(5) x_axis: vec2<f32>,
+    @location(6) y_axis: vec2<f32>,
+    @location(7) translation: vec2<f32>,
+    @location(8) origin: vec2<f32>,
+    @location(9) size: vec2<f32>,
+    @location(10) uv_rect: vec4<f32>,
+    @location(11) tint: vec4<f32>,
+    @location(12) flash: f32,
+    @location(13) flip: vec2<f32>,
 }
 
-struct Dimensions {
-    width: f32,
-    height: f32,
+struct Camera {
+    view_proj: mat4x4<f32>,
 }
 
+@group(1) @binding(0)
+var<uniform> camera: Camera;
+
 // Trig function in radians
 @vertex
 fn vs_main(
     model: VertexInput,
     entity: EntityInput,
 ) -> VertexOutput {
-    // TEMP, THESE WILL BE PASSED INTO SHADER SOMEHOW
-    var screen_width = 562.0;
-    var screen_height = 1021.0;
     var out: VertexOutput;
-    var rot_mat = mat2x2<f32>(
-        entity.rotation_1,
-        entity.rotation_2,
-    );
-    var scale_mat = mat2x2<f32>(
-        entity.scale_1,
-        entity.scale_2,
-    );
-    var orig_vec = vec2<f32>(model.position.x- entity.origin.x, model.position.y - entity.origin.y);
-    orig_vec = (orig_vec * rot_mat * scale_mat) + entity.position + entity.origin;
-    orig_vec = normalise(orig_vec, screen_width, screen_height);
-    out.tex_coords = model.tex_coords;
-    out.clip_position = vec4<f32>(orig_vec, 1.0, 1.0);
+    // the quad is a unit square, stretch it to the sprite's size in pixels
+    var local = model.position.xy * entity.size - entity.origin;
+    var world = entity.x_axis * local.x + entity.y_axis * local.y + entity.translation;
+    // mirror the quad's 0..1 tex coords where flipped, then map them into the sprite's
+    // part of the texture
+    var tex_coords = mix(model.tex_coords, 1.0 - model.tex_coords, entity.flip);
+    out.tex_coords = entity.uv_rect.xy + tex_coords * entity.uv_rect.zw;
+    out.tint = entity.tint;
+    out.flash = entity.flash;
+    out.clip_position = camera.view_proj * vec4<f32>(world, 0.0, 1.0);
     return out;
 }
 
-fn normalise(given: vec2<f32>, width: f32, height: f32) -> vec2<f32> {
-    return vec2<f32>(((2.0 * (given.x)) / width) - 1.0, ((2.0 * (given.y)) / height) - 1.0);
-}
-
 // Fragment shader
 
 @group(0) @binding(0)
@@ -61,5 +59,7 @@ var s_diffuse: sampler;
 
 @fragment
 fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
-    return textureSample(t_diffuse, s_diffuse, in.tex_coords);
+    var color = textureSample(t_diffuse, s_diffuse, in.tex_coords) * in.tint;
+    // flash is added on top, only where the sprite isn't transparent
+    return vec4<f32>(min(color.rgb + vec3<f32>(in.flash), vec3<f32>(1.0)), color.a);
 }
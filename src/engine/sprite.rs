@@ -1,97 +1,105 @@
-use crate::engine::texture;
+use crate::engine::assets::TextureHandle;
 use crate::engine::vertex::Vertex;
 use cgmath::Vector2;
-use wgpu::util::DeviceExt;
+use std::rc::Rc;
+
+// Every sprite is drawn with the same unit square, scaled to the sprite's size per instance.
+// tex_coords go 0..1 across it and get mapped into the sprite's uv_rect in the shader.
+pub const QUAD_VERTICES: [Vertex; 4] = [
+    Vertex {
+        position: [0.5, 0.5, 0.0],
+        tex_coords: [1.0, 0.0],
+    },
+    Vertex {
+        position: [-0.5, 0.5, 0.0],
+        tex_coords: [0.0, 0.0],
+    },
+    Vertex {
+        position: [-0.5, -0.5, 0.0],
+        tex_coords: [0.0, 1.0],
+    },
+    Vertex {
+        position: [0.5, -0.5, 0.0],
+        tex_coords: [1.0, 1.0],
+    },
+];
 
-// contains texture and texture bind group used for drawing
-// vertices are just a rectangle to fit an image
 // indicies form the two triangles to actually be drawn by the gpu.
+pub const QUAD_INDICES: [u16; 6] = [0, 1, 2, 0, 2, 3];
+
+// A rectangle of a texture, either the whole image or one region of an atlas page.
+// Sprites cut from the same texture share its bind group, so the batch can draw them together.
 pub struct Sprite {
-    pub diffuse_texture: texture::Texture,
-    pub diffuse_bind_group: wgpu::BindGroup,
-    pub vertices: [Vertex; 4],
-    pub vertex_buffer: wgpu::Buffer,
-    pub index_buffer: wgpu::Buffer,
-    pub indices: [u16; 6],
+    pub diffuse_texture: TextureHandle,
+    pub diffuse_bind_group: Rc<wgpu::BindGroup>,
+    // x, y, width, height of the region in texture coordinates
+    pub uv_rect: [f32; 4],
+    // size of the region in pixels
+    pub size: Vector2<f32>,
     pub origin: Vector2<f32>,
 }
 
 impl Sprite {
+    // A sprite showing the whole texture.
     pub fn new(
-        filepath: &str,
+        diffuse_texture: TextureHandle,
         texture_bind_group_layout: &wgpu::BindGroupLayout,
         device: &wgpu::Device,
-        queue: &wgpu::Queue,
     ) -> Self {
-        let image_file = image::open(filepath).unwrap();
-        let diffuse_texture =
-            texture::Texture::from_image(device, queue, &image_file, Some(filepath)).unwrap();
-        let diffuse_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
-            layout: &texture_bind_group_layout,
-            entries: &[
-                wgpu::BindGroupEntry {
-                    binding: 0,
-                    resource: wgpu::BindingResource::TextureView(&diffuse_texture.view), // CHANGED!
-                },
-                wgpu::BindGroupEntry {
-                    binding: 1,
-                    resource: wgpu::BindingResource::Sampler(&diffuse_texture.sampler), // CHANGED!
-                },
-            ],
-            label: Some("diffuse_bind_group"),
-        });
-        let (origin, vertices) = Sprite::create_vetices(image_file.width(), image_file.height());
-        let indices = [0, 1, 2, 0, 2, 3];
-        let vertex_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-            label: Some("Vertex Buffer"),
-            contents: bytemuck::cast_slice(&vertices),
-            usage: wgpu::BufferUsages::VERTEX,
-        });
+        let diffuse_bind_group = Rc::new(Sprite::create_bind_group(&diffuse_texture, texture_bind_group_layout, device));
+        let width = diffuse_texture.texture.width();
+        let height = diffuse_texture.texture.height();
+        Sprite::from_region(diffuse_texture, diffuse_bind_group, 0, 0, width, height)
+    }
 
-        let index_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-            label: Some("Index Buffer"),
-            contents: bytemuck::cast_slice(&indices),
-            usage: wgpu::BufferUsages::INDEX,
-        });
+    // A sprite showing the given pixel rectangle of the texture, bind_group must be one
+    // created for that texture.
+    pub fn from_region(
+        diffuse_texture: TextureHandle,
+        diffuse_bind_group: Rc<wgpu::BindGroup>,
+        x: u32,
+        y: u32,
+        width: u32,
+        height: u32,
+    ) -> Self {
+        let texture_width = diffuse_texture.texture.width() as f32;
+        let texture_height = diffuse_texture.texture.height() as f32;
+        let uv_rect = [
+            x as f32 / texture_width,
+            y as f32 / texture_height,
+            width as f32 / texture_width,
+            height as f32 / texture_height,
+        ];
         Sprite {
             diffuse_texture,
             diffuse_bind_group,
-            vertices,
-            vertex_buffer,
-            index_buffer,
-            indices,
-            origin,
+            uv_rect,
+            size: Vector2 {
+                x: width as f32,
+                y: height as f32,
+            },
+            origin: Vector2 { x: 0.0, y: 0.0 },
         }
     }
 
-    fn create_vetices(width: u32, height: u32) -> (Vector2<f32>, [Vertex; 4]) {
-        let width = width as f32;
-        let height = height as f32;
-        let h_width = width / 2.0;
-        let h_height = height / 2.0;
-        (
-            Vector2 {
-                x: (width / 2.0) - h_width,
-                y: (height / 2.0) - h_height,
-            },
-            [
-                Vertex {
-                    position: [width - h_width, height - h_height, 0.0],
-                    tex_coords: [1.0, 0.0],
-                },
-                Vertex {
-                    position: [0.0 - h_width, height - h_height, 0.0],
-                    tex_coords: [0.0, 0.0],
-                },
-                Vertex {
-                    position: [0.0 - h_width, 0.0 - h_height, 0.0],
-                    tex_coords: [0.0, 1.0],
+    pub fn create_bind_group(
+        texture: &TextureHandle,
+        texture_bind_group_layout: &wgpu::BindGroupLayout,
+        device: &wgpu::Device,
+    ) -> wgpu::BindGroup {
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: texture_bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&texture.view),
                 },
-                Vertex {
-                    position: [width - h_width, 0.0 - h_height, 0.0],
-                    tex_coords: [1.0, 1.0],
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(&texture.sampler),
                 },
             ],
-        )
+            label: Some("diffuse_bind_group"),
+        })
     }
 }
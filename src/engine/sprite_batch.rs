@@ -0,0 +1,187 @@
+use crate::engine::assets::SpriteHandle;
+use crate::engine::camera::{Camera2D, CameraUniform};
+use crate::engine::entity::EntityRaw;
+use crate::engine::gpu::Gpu;
+use std::rc::Rc;
+
+enum BatchCommand {
+    // a single sprite, its instance data goes into the batch's own buffer
+    Sprite { raw: EntityRaw },
+    // an entity group, already uploaded to its own persistent buffer
+    Instances { buffer: Rc<wgpu::Buffer>, count: u32 },
+}
+
+struct DrawCommand {
+    sprite: SpriteHandle,
+    layer: i32,
+    command: BatchCommand,
+}
+
+// Collects everything drawn in a frame and issues it all in one render pass.
+// Commands are sorted by layer then texture, so consecutive sprites that share a texture end
+// up in a single instanced draw call, the number of draw calls follows the number of
+// textures on screen rather than the number of objects. Sprites packed into the same atlas
+// page count as one texture.
+pub struct SpriteBatch {
+    commands: Vec<DrawCommand>,
+    render_pipeline: Rc<wgpu::RenderPipeline>,
+    instance_buffer: wgpu::Buffer,
+    capacity: usize,
+    camera_buffer: wgpu::Buffer,
+    camera_bind_group: wgpu::BindGroup,
+    draw_calls: usize,
+}
+
+// Starting size of the instance buffer, it doubles whenever a frame needs more.
+const INITIAL_CAPACITY: usize = 256;
+
+impl SpriteBatch {
+    pub fn new(gpu: &Gpu) -> Self {
+        let camera_buffer = gpu.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Camera Buffer"),
+            size: std::mem::size_of::<CameraUniform>() as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let camera_bind_group = gpu.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: gpu.render_cache.camera_bind_group_layout(),
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: camera_buffer.as_entire_binding(),
+            }],
+            label: Some("camera_bind_group"),
+        });
+        SpriteBatch {
+            commands: Vec::new(),
+            render_pipeline: gpu.render_pipeline(),
+            instance_buffer: SpriteBatch::create_instance_buffer(&gpu.device, INITIAL_CAPACITY),
+            capacity: INITIAL_CAPACITY,
+            camera_buffer,
+            camera_bind_group,
+            draw_calls: 0,
+        }
+    }
+
+    fn create_instance_buffer(device: &wgpu::Device, capacity: usize) -> wgpu::Buffer {
+        device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Sprite Batch Instance Buffer"),
+            size: (capacity * std::mem::size_of::<EntityRaw>()) as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        })
+    }
+
+    // Lower layers are drawn first.
+    pub fn draw(&mut self, sprite: &SpriteHandle, raw: EntityRaw, layer: i32) {
+        self.commands.push(DrawCommand {
+            sprite: sprite.clone(),
+            layer,
+            command: BatchCommand::Sprite { raw },
+        });
+    }
+
+    // Draws count instances straight out of a buffer laid out like EntityRaw.
+    pub fn draw_instances(&mut self, sprite: &SpriteHandle, buffer: Rc<wgpu::Buffer>, count: u32, layer: i32) {
+        if count == 0 {
+            return;
+        }
+        self.commands.push(DrawCommand {
+            sprite: sprite.clone(),
+            layer,
+            command: BatchCommand::Instances { buffer, count },
+        });
+    }
+
+    // Sorts and draws everything collected since the last flush into view, as seen by camera.
+    // clear is the colour to clear the view to first, or None to draw over what's there.
+    pub fn flush(
+        &mut self,
+        gpu: &Gpu,
+        encoder: &mut wgpu::CommandEncoder,
+        view: &wgpu::TextureView,
+        camera: &Camera2D,
+        clear: Option<wgpu::Color>,
+    ) {
+        gpu.queue.write_buffer(&self.camera_buffer, 0, bytemuck::cast_slice(&[camera.to_uniform()]));
+
+        // stable, so things on the same layer with the same texture keep the order they came in
+        self.commands
+            .sort_by_key(|command| (command.layer, command.sprite.diffuse_texture.id()));
+
+        let instance_data: Vec<EntityRaw> = self
+            .commands
+            .iter()
+            .filter_map(|command| match command.command {
+                BatchCommand::Sprite { raw } => Some(raw),
+                BatchCommand::Instances { .. } => None,
+            })
+            .collect();
+        if instance_data.len() > self.capacity {
+            while instance_data.len() > self.capacity {
+                self.capacity *= 2;
+            }
+            self.instance_buffer = SpriteBatch::create_instance_buffer(&gpu.device, self.capacity);
+        }
+        if !instance_data.is_empty() {
+            gpu.queue.write_buffer(&self.instance_buffer, 0, bytemuck::cast_slice(&instance_data));
+        }
+
+        let load = match clear {
+            Some(color) => wgpu::LoadOp::Clear(color),
+            None => wgpu::LoadOp::Load,
+        };
+        let mut draw_calls = 0;
+        {
+            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Sprite Batch Pass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view,
+                    resolve_target: None,
+                    ops: wgpu::Operations { load, store: true },
+                })],
+                depth_stencil_attachment: None,
+            });
+            render_pass.set_pipeline(&self.render_pipeline);
+            render_pass.set_bind_group(1, &self.camera_bind_group, &[]);
+            render_pass.set_vertex_buffer(0, gpu.render_cache.quad_vertex_buffer().slice(..));
+            render_pass.set_index_buffer(gpu.render_cache.quad_index_buffer().slice(..), wgpu::IndexFormat::Uint16);
+
+            let mut next_instance = 0;
+            let mut i = 0;
+            while i < self.commands.len() {
+                let sprite = &self.commands[i].sprite;
+                render_pass.set_bind_group(0, &sprite.diffuse_bind_group, &[]);
+                match &self.commands[i].command {
+                    BatchCommand::Instances { buffer, count } => {
+                        render_pass.set_vertex_buffer(1, buffer.slice(..));
+                        render_pass.draw_indexed(0..6, 0, 0..*count);
+                        i += 1;
+                    }
+                    BatchCommand::Sprite { .. } => {
+                        // every sprite command in a row with the same texture and layer is one draw
+                        let first = next_instance;
+                        let layer = self.commands[i].layer;
+                        while i < self.commands.len()
+                            && self.commands[i].layer == layer
+                            && self.commands[i].sprite.diffuse_texture.ptr_eq(&sprite.diffuse_texture)
+                            && matches!(self.commands[i].command, BatchCommand::Sprite { .. })
+                        {
+                            next_instance += 1;
+                            i += 1;
+                        }
+                        render_pass.set_vertex_buffer(1, self.instance_buffer.slice(..));
+                        render_pass.draw_indexed(0..6, 0, first..next_instance);
+                    }
+                }
+                draw_calls += 1;
+            }
+        }
+        self.draw_calls = draw_calls;
+        self.commands.clear();
+    }
+
+    // How many draw calls the last flush took.
+    pub fn draw_calls(&self) -> usize {
+        self.draw_calls
+    }
+}
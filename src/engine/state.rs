@@ -1,91 +1,56 @@
-use crate::engine::entity::Entity;
-use crate::engine::entity::EntityRaw;
-use crate::engine::sprite::Sprite;
-use crate::engine::texture;
-use crate::engine::vertex::Vertex;
-use cgmath::prelude::*;
-use wgpu::util::DeviceExt;
+use crate::engine::canvas::VirtualCanvas;
+use crate::engine::context::Context;
+use crate::engine::error::EngineError;
+use crate::engine::error::Result;
+use crate::engine::game::Game;
+use crate::engine::gpu::Gpu;
+use crate::engine::sprite_batch::SpriteBatch;
+use crate::engine::timestep::FixedTimestep;
 use winit::event::WindowEvent;
 use winit::window::Window;
-use crate::engine::entity_group::EntityGroup;
-use crate::actors::player::Player;
-use crate::actors::enemy::Enemy;
-use crate::actors::bullet::Bullet;
-use crate::engine::draw::Draw;
-use winit::event::KeyboardInput;
-use winit::event::ElementState;
-use winit::event::VirtualKeyCode;
-use std::time::Instant;
-use std::time::Duration;
-use rand::Rng;
 
-pub struct State {
+pub struct State<G: Game> {
     pub surface: wgpu::Surface,
     pub config: wgpu::SurfaceConfiguration,
     pub size: winit::dpi::PhysicalSize<u32>,
-    pub player: Player,
-    pub enemies: Vec<Enemy>,
-    pub bullets: Vec<Bullet>,
     pub window: Window,
-    pub device: wgpu::Device,
-    pub queue: wgpu::Queue,
-    pub instant: Instant,
-    pub duration: Duration,
-    pub adapter: wgpu::Adapter,
+    pub ctx: Context,
+    pub game: G,
+    pub batch: SpriteBatch,
+    pub canvas: VirtualCanvas,
+    pub timestep: FixedTimestep,
 }
 
-static mut rotation: f32 = 0.0;
+// Ticks per second the gameplay is simulated at, independent of the display.
+pub const DEFAULT_TICK_RATE: u32 = 60;
 
-impl State {
+impl<G: Game> State<G> {
     // Creating some of the wgpu types requires async code
-    pub async fn new(window: Window) -> Self {
+    pub async fn new(window: Window, mut game: G) -> Result<Self> {
+        let (virtual_width, virtual_height) = game.virtual_size();
         window.set_inner_size(winit::dpi::PhysicalSize {
-            width: 562,
-            height: 1021,
+            width: virtual_width,
+            height: virtual_height,
         });
-        window.set_resizable(false);
+        window.set_resizable(true);
         let size = window.inner_size();
-        // println!("size: {:?}", window.inner_size());
 
         let instance = wgpu::Instance::new(wgpu::InstanceDescriptor {
             backends: wgpu::Backends::all(),
             dx12_shader_compiler: Default::default(),
         });
 
-        let surface = unsafe { instance.create_surface(&window) }.unwrap();
+        let surface = unsafe { instance.create_surface(&window) }?;
 
-        let adapter = instance
-            .request_adapter(&wgpu::RequestAdapterOptions {
-                power_preference: wgpu::PowerPreference::default(),
-                compatible_surface: Some(&surface),
-                force_fallback_adapter: false,
-            })
+        let adapter = Gpu::request_adapter(&instance, Some(&surface), false)
             .await
-            .unwrap();
-        let (device, queue) = adapter
-            .request_device(
-                &wgpu::DeviceDescriptor {
-                    features: wgpu::Features::empty(),
-                    // WebGL doesn't support all of wgpu's features, so if
-                    // we're building for the web we'll have to disable some.
-                    limits: if cfg!(target_arch = "wasm32") {
-                        wgpu::Limits::downlevel_webgl2_defaults()
-                    } else {
-                        wgpu::Limits::default()
-                    },
-                    label: None,
-                },
-                None, // Trace path
-            )
-            .await
-            .unwrap();
+            .ok_or(EngineError::NoAdapter)?;
         let surface_caps = surface.get_capabilities(&adapter);
         let surface_format = surface_caps
             .formats
             .iter()
             .copied()
-            .filter(|f| f.describe().srgb)
-            .next()
+            .find(|f| f.describe().srgb)
             .unwrap_or(surface_caps.formats[0]);
         let config = wgpu::SurfaceConfiguration {
             usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
@@ -96,153 +61,99 @@ impl State {
             alpha_mode: surface_caps.alpha_modes[0],
             view_formats: vec![],
         };
-        surface.configure(&device, &config);
-        let player = Player::new("assets/player.png", 200, 200, 0.0, 4.0, &surface, &config, &adapter, &queue, &device);
-        let enemies: Vec<Enemy> = Vec::new();
-        let bullets: Vec<Bullet> = Vec::new();
-
+        let gpu = Gpu::new(adapter, surface_format).await?;
+        surface.configure(&gpu.device, &config);
+        let mut ctx = Context::new(gpu, virtual_width, virtual_height, game.scaling());
+        // the window manager doesn't have to give us the size asked for
+        ctx.resize(size.width, size.height);
+        // no pads is better than no game, e.g. when udev isn't available
+        #[cfg(feature = "gamepad")]
+        match crate::engine::gamepad::GilrsBackend::new() {
+            Ok(backend) => ctx.input.set_gamepad_backend(Box::new(backend)),
+            Err(err) => log::warn!("{}", err),
+        }
+        game.init(&mut ctx)?;
+        let batch = SpriteBatch::new(&ctx.gpu);
+        let canvas = VirtualCanvas::new(&ctx.gpu, virtual_width, virtual_height);
 
-        // ...
-        let instant = Instant::now();
-        let duration = instant.elapsed();
-        Self {
+        let timestep = FixedTimestep::new(DEFAULT_TICK_RATE)?;
+        Ok(Self {
             window,
             surface,
             config,
             size,
-            player,
-            enemies,
-            bullets,
-            device,
-            queue,
-            instant,
-            duration,
-            adapter,
-        }
+            ctx,
+            game,
+            batch,
+            canvas,
+            timestep,
+        })
     }
 
     pub fn window(&self) -> &Window {
         &self.window
     }
 
+    // A minimised window reports a size of zero, the surface can't be configured to that
+    // so it's ignored until the window comes back.
     pub fn resize(&mut self, new_size: winit::dpi::PhysicalSize<u32>) {
-        /*
         if new_size.width > 0 && new_size.height > 0 {
             self.size = new_size;
             self.config.width = new_size.width;
             self.config.height = new_size.height;
-            self.surface.configure(&self.device, &self.config);
+            self.surface.configure(&self.ctx.gpu.device, &self.config);
+            self.ctx.resize(new_size.width, new_size.height);
         }
-        */
     }
 
     pub fn input(&mut self, event: &WindowEvent) -> bool {
-        match event {
-            WindowEvent::KeyboardInput {
-                input: KeyboardInput {
-                    state,
-                    virtual_keycode: Some(keycode),
-                    ..
-                },
-                ..
-            } => {
-                let pressed = *state == ElementState::Pressed;
-                match keycode {
-                    VirtualKeyCode::Up => {
-                        self.player.up = pressed;
-                        true
-                    }
-                    VirtualKeyCode::Down => {
-                        self.player.down = pressed;
-                        true
-                    }
-                    VirtualKeyCode::Left => {
-                        self.player.left = pressed;
-                        true
-                    }
-                    VirtualKeyCode::Right => {
-                        self.player.right = pressed;
-                        true
-                    }
-                    _ => false,
-                }
-            }
-            _ => false,
+        if self.ctx.input.handle_event(event) {
+            return true;
         }
+        self.game.input(&mut self.ctx, event)
+    }
 
+    pub fn set_tick_rate(&mut self, tick_rate: u32) -> Result<()> {
+        self.timestep.set_tick_rate(tick_rate)
     }
 
-    pub fn update(&mut self) {
-        self.duration = self.instant.elapsed();
-        self.instant = Instant::now();
-        self.player.update(&self.duration, self.window.inner_size().width, self.window.inner_size().height);
-        self.enemies.retain_mut(|x| x.alive());
-        self.bullets.retain_mut(|x| x.alive());
-        let inner_size = self.window.inner_size();
-        for bullet in &mut self.bullets {
-            bullet.update(&self.duration, inner_size.height as f32);
-        }
-        if self.bullets.len() < 1 {
-            self.bullets.push(Bullet::new("assets/bullet.png", self.player.x(), self.player.y(), 0.0, 2.0, &self.surface, &self.config, &self.adapter, &self.queue, &self.device));
-        }
-        for enemy in &mut self.enemies {
-            enemy.update(&self.duration, inner_size.height as f32, inner_size.width as f32);
-        }
-        if self.enemies.len() < 15 {
-            let mut rng = rand::thread_rng();
-            let x: u32 = rng.gen_range(0..self.window.inner_size().width);
-            let y: u32 = rng.gen_range(self.window.inner_size().height..self.window.inner_size().height * 2);
-            let velocity: f64 = rng.gen_range(300.0..700.0);
-            self.enemies.push(Enemy::new("assets/enemy.png", x, y, velocity, 0.0, 2.0, &self.surface, &self.config, &self.adapter, &self.queue, &self.device));
-        }
-        for enemy in &mut self.enemies {
-            for bullet in &mut self.bullets {
-                if enemy.get_collision().check_collision(bullet.get_collision()) {
-                    bullet.kill();
-                    enemy.kill();
-                    break;
-                }
-            }
+    // Runs as many fixed ticks as real time calls for, render() interpolates what's left over.
+    pub fn update(&mut self) -> Result<()> {
+        let ticks = self.timestep.advance_realtime();
+        for _ in 0..ticks {
+            self.ctx.input.update();
+            self.game.update(&mut self.ctx, self.timestep.tick())?;
+            self.ctx.camera.update(self.timestep.tick());
         }
+        Ok(())
+    }
+
+    pub fn finished(&self) -> bool {
+        self.ctx.should_exit()
     }
 
-    pub fn render(&mut self) -> Result<(), wgpu::SurfaceError> {
+    pub fn render(&mut self) -> std::result::Result<(), wgpu::SurfaceError> {
         let output = self.surface.get_current_texture()?;
         let view = output
             .texture
             .create_view(&wgpu::TextureViewDescriptor::default());
         let mut encoder = self
+            .ctx
+            .gpu
             .device
             .create_command_encoder(&wgpu::CommandEncoderDescriptor {
                 label: Some("Render Encoder"),
             });
-        {
-            let _render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
-                label: Some("Render Pass"),
-                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-                    view: &view,
-                    resolve_target: None,
-                    ops: wgpu::Operations {
-                        load: wgpu::LoadOp::Clear(wgpu::Color {
-                            r: 0.0,
-                            g: 0.0,
-                            b: 0.0,
-                            a: 1.0,
-                        }),
-                        store: true,
-                    },
-                })],
-                depth_stencil_attachment: None,
-            });
-        }
-        for bullet in &mut self.bullets {
-            bullet.draw(&self.device, &mut encoder, &view).unwrap();
-        }
-        for enemy in &mut self.enemies {
-            enemy.draw(&self.device, &mut encoder, &view).unwrap();
-        }
-        self.player.draw(&self.device, &mut encoder, &view).unwrap();
-        self.queue.submit(std::iter::once(encoder.finish()));
+        self.game.render(&self.ctx, self.timestep.alpha(), &mut self.batch);
+        self.batch.flush(
+            &self.ctx.gpu,
+            &mut encoder,
+            self.canvas.view(),
+            &self.ctx.camera,
+            Some(wgpu::Color::BLACK),
+        );
+        self.canvas.blit(&mut encoder, &view, self.ctx.letterbox());
+        self.ctx.gpu.queue.submit(std::iter::once(encoder.finish()));
         output.present();
 
         Ok(())
@@ -1,4 +1,5 @@
-use anyhow::*;
+use crate::engine::error::EngineError;
+use crate::engine::error::Result;
 use image::GenericImageView;
 
 pub struct Texture {
@@ -8,13 +9,24 @@ pub struct Texture {
 }
 
 impl Texture {
+    pub fn from_file(device: &wgpu::Device, queue: &wgpu::Queue, path: &str) -> Result<Self> {
+        let bytes = std::fs::read(path).map_err(|source| EngineError::Io {
+            path: path.to_string(),
+            source,
+        })?;
+        Self::from_bytes(device, queue, &bytes, path)
+    }
+
     pub fn from_bytes(
         device: &wgpu::Device,
         queue: &wgpu::Queue,
         bytes: &[u8],
         label: &str,
     ) -> Result<Self> {
-        let img = image::load_from_memory(bytes)?;
+        let img = image::load_from_memory(bytes).map_err(|source| EngineError::Decode {
+            path: label.to_string(),
+            source,
+        })?;
         Self::from_image(device, queue, &img, Some(label))
     }
 
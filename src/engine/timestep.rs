@@ -0,0 +1,123 @@
+use crate::engine::error::{EngineError, Result};
+use std::time::Duration;
+use std::time::Instant;
+
+// Accumulates real time and hands it back out in fixed size ticks, so the simulation steps
+// the same amount no matter the frame rate or vsync.
+// Rendering uses alpha() to interpolate between the last two ticks.
+pub struct FixedTimestep {
+    tick_rate: u32,
+    tick: Duration,
+    accumulator: Duration,
+    last: Instant,
+    // Upper bound on ticks per frame, stops a slow frame from snowballing into slower ones.
+    max_ticks: u32,
+}
+
+impl FixedTimestep {
+    // tick_rate is ticks per second and can't be zero.
+    pub fn new(tick_rate: u32) -> Result<Self> {
+        Ok(FixedTimestep {
+            tick_rate,
+            tick: tick_length(tick_rate)?,
+            accumulator: Duration::ZERO,
+            last: Instant::now(),
+            max_ticks: 8,
+        })
+    }
+
+    // Adds the real time since the last call and returns how many ticks should be simulated.
+    pub fn advance_realtime(&mut self) -> u32 {
+        let now = Instant::now();
+        let elapsed = now - self.last;
+        self.last = now;
+        self.advance(elapsed)
+    }
+
+    // Like advance_realtime() with the frame's length given, e.g. from a replay.
+    pub fn advance(&mut self, elapsed: Duration) -> u32 {
+        self.accumulator += elapsed;
+        let mut ticks = 0;
+        while self.accumulator >= self.tick && ticks < self.max_ticks {
+            self.accumulator -= self.tick;
+            ticks += 1;
+        }
+        // Drop whatever couldn't be caught up on instead of carrying it into the next frame.
+        if self.accumulator >= self.tick {
+            self.accumulator = Duration::ZERO;
+        }
+        ticks
+    }
+
+    // How far between the previous tick and the next one the current frame is, 0.0 to 1.0.
+    pub fn alpha(&self) -> f32 {
+        (self.accumulator.as_secs_f64() / self.tick.as_secs_f64()).min(1.0) as f32
+    }
+
+    pub fn set_tick_rate(&mut self, tick_rate: u32) -> Result<()> {
+        self.tick = tick_length(tick_rate)?;
+        self.tick_rate = tick_rate;
+        Ok(())
+    }
+
+    pub fn tick_rate(&self) -> u32 {
+        self.tick_rate
+    }
+
+    pub fn tick(&self) -> Duration {
+        self.tick
+    }
+}
+
+fn tick_length(tick_rate: u32) -> Result<Duration> {
+    if tick_rate == 0 {
+        return Err(EngineError::InvalidTickRate);
+    }
+    Ok(Duration::from_secs_f64(1.0 / tick_rate as f64))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ms(ms: u64) -> Duration {
+        Duration::from_millis(ms)
+    }
+
+    #[test]
+    fn zero_tick_rate_is_an_error() {
+        assert!(matches!(FixedTimestep::new(0), Err(EngineError::InvalidTickRate)));
+        let mut timestep = FixedTimestep::new(60).unwrap();
+        assert!(timestep.set_tick_rate(0).is_err());
+        // a bad rate leaves the old one alone
+        assert_eq!(timestep.tick_rate(), 60);
+    }
+
+    #[test]
+    fn hands_out_whole_ticks_and_keeps_the_rest() {
+        let mut timestep = FixedTimestep::new(100).unwrap();
+        assert_eq!(timestep.advance(ms(5)), 0);
+        assert_eq!(timestep.advance(ms(5)), 1);
+        assert_eq!(timestep.advance(ms(35)), 3);
+        // 5ms left over
+        assert_eq!(timestep.advance(ms(5)), 1);
+    }
+
+    #[test]
+    fn slow_frames_are_clamped() {
+        let mut timestep = FixedTimestep::new(100).unwrap();
+        assert_eq!(timestep.advance(ms(1000)), 8);
+        // the rest was dropped, not carried over
+        assert_eq!(timestep.advance(ms(0)), 0);
+        assert_eq!(timestep.alpha(), 0.0);
+    }
+
+    #[test]
+    fn alpha_is_how_far_into_the_next_tick() {
+        let mut timestep = FixedTimestep::new(100).unwrap();
+        timestep.advance(ms(5));
+        assert!((timestep.alpha() - 0.5).abs() < 1e-4);
+        timestep.advance(ms(7));
+        assert!((timestep.alpha() - 0.2).abs() < 1e-4);
+    }
+}
@@ -1,37 +1,223 @@
-use std::f32::consts::PI;
+use cgmath::{Deg, Vector2};
+use cgmath::prelude::*;
+use std::ops::Mul;
 
-#[repr(C)]
-#[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+// A 2D affine transform as a 3x2 matrix: the columns are where the local x and y axes end
+// up and where the local origin ends up.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Affine2 {
+    pub x_axis: Vector2<f32>,
+    pub y_axis: Vector2<f32>,
+    pub translation: Vector2<f32>,
+}
+
+impl Affine2 {
+    pub fn identity() -> Self {
+        Affine2 {
+            x_axis: Vector2 { x: 1.0, y: 0.0 },
+            y_axis: Vector2 { x: 0.0, y: 1.0 },
+            translation: Vector2 { x: 0.0, y: 0.0 },
+        }
+    }
+
+    pub fn transform_point(&self, point: Vector2<f32>) -> Vector2<f32> {
+        self.transform_vector(point) + self.translation
+    }
+
+    // Like transform_point but ignores the translation, for directions and sizes.
+    pub fn transform_vector(&self, vector: Vector2<f32>) -> Vector2<f32> {
+        self.x_axis * vector.x + self.y_axis * vector.y
+    }
+
+    // None if the transform squashes everything flat (a scale of zero).
+    pub fn inverse(&self) -> Option<Affine2> {
+        let determinant = self.x_axis.x * self.y_axis.y - self.y_axis.x * self.x_axis.y;
+        if determinant == 0.0 {
+            return None;
+        }
+        let x_axis = Vector2 {
+            x: self.y_axis.y,
+            y: -self.x_axis.y,
+        } / determinant;
+        let y_axis = Vector2 {
+            x: -self.y_axis.x,
+            y: self.x_axis.x,
+        } / determinant;
+        let inverse = Affine2 {
+            x_axis,
+            y_axis,
+            translation: Vector2 { x: 0.0, y: 0.0 },
+        };
+        Some(Affine2 {
+            translation: -inverse.transform_vector(self.translation),
+            ..inverse
+        })
+    }
+
+    // Columns in the order the instance layout wants them.
+    pub fn to_cols(&self) -> [[f32; 2]; 3] {
+        [self.x_axis.into(), self.y_axis.into(), self.translation.into()]
+    }
+}
+
+// parent * child is the child's transform in the parent's space.
+impl Mul for Affine2 {
+    type Output = Affine2;
+
+    fn mul(self, child: Affine2) -> Affine2 {
+        Affine2 {
+            x_axis: self.transform_vector(child.x_axis),
+            y_axis: self.transform_vector(child.y_axis),
+            translation: self.transform_point(child.translation),
+        }
+    }
+}
+
+// Position, rotation, scale and skew as set by game code, turned into a matrix for drawing
+// and collision with matrix(). Applied scale first, then skew, rotation and translation.
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub struct Transformation {
-    rotation: [[f32; 2]; 2],
-    scale: [[f32; 2]; 2],
+    pub translation: Vector2<f32>,
+    // degrees, clockwise
+    pub rotation: f32,
+    pub scale: Vector2<f32>,
+    // degrees, x leans the sprite sideways as y goes up, y does the same the other way
+    pub skew: Vector2<f32>,
 }
 
 impl Transformation {
     pub fn new(rotation: f32, scale: f32) -> Transformation {
-        let rotation = (rotation * PI) / 180.0;
-        let rotation = [
-            [rotation.cos(), rotation.sin()],
-            [-(rotation.sin()), rotation.cos()],
-        ];
-        let scale = [[scale, 0.0], [0.0, scale]];
-        Transformation { rotation, scale }
+        Transformation {
+            translation: Vector2 { x: 0.0, y: 0.0 },
+            rotation,
+            scale: Vector2 { x: scale, y: scale },
+            skew: Vector2 { x: 0.0, y: 0.0 },
+        }
     }
 
     pub fn update(&mut self, rotation: f32, scale: f32) {
-        let rotation = (rotation * PI) / 180.0;
-        self.rotation = [
-            [rotation.cos(), rotation.sin()],
-            [-(rotation.sin()), rotation.cos()],
-        ];
-        self.scale = [[scale, 0.0], [0.0, scale]];
+        self.rotation = rotation;
+        self.scale = Vector2 { x: scale, y: scale };
+    }
+
+    pub fn matrix(&self) -> Affine2 {
+        let (sin, cos) = Deg(self.rotation).sin_cos();
+        let rotation = Affine2 {
+            x_axis: Vector2 { x: cos, y: -sin },
+            y_axis: Vector2 { x: sin, y: cos },
+            translation: self.translation,
+        };
+        let skew = Affine2 {
+            x_axis: Vector2 {
+                x: 1.0,
+                y: Deg(self.skew.y).tan(),
+            },
+            y_axis: Vector2 {
+                x: Deg(self.skew.x).tan(),
+                y: 1.0,
+            },
+            translation: Vector2 { x: 0.0, y: 0.0 },
+        };
+        let scale = Affine2 {
+            x_axis: Vector2 {
+                x: self.scale.x,
+                y: 0.0,
+            },
+            y_axis: Vector2 {
+                x: 0.0,
+                y: self.scale.y,
+            },
+            translation: Vector2 { x: 0.0, y: 0.0 },
+        };
+        rotation * skew * scale
+    }
+}
+
+impl Default for Transformation {
+    fn default() -> Self {
+        Self::new(0.0, 1.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(a: Vector2<f32>, b: Vector2<f32>) {
+        assert!((a - b).magnitude() < 1e-4, "{:?} != {:?}", a, b);
+    }
+
+    fn assert_affine_close(a: Affine2, b: Affine2) {
+        assert_close(a.x_axis, b.x_axis);
+        assert_close(a.y_axis, b.y_axis);
+        assert_close(a.translation, b.translation);
+    }
+
+    fn transformation(translation: Vector2<f32>, rotation: f32, scale: Vector2<f32>, skew: Vector2<f32>) -> Transformation {
+        Transformation {
+            translation,
+            rotation,
+            scale,
+            skew,
+        }
+    }
+
+    fn translation(x: f32, y: f32) -> Affine2 {
+        Affine2 {
+            translation: Vector2 { x, y },
+            ..Affine2::identity()
+        }
+    }
+
+    #[test]
+    fn parent_times_child_puts_the_child_in_the_parents_space() {
+        // turned a quarter clockwise, so the child's right is the parent's down
+        let parent = transformation(Vector2 { x: 100.0, y: 0.0 }, 90.0, Vector2 { x: 2.0, y: 2.0 }, Vector2 { x: 0.0, y: 0.0 }).matrix();
+        let child = translation(10.0, 0.0);
+        assert_close((parent * child).translation, Vector2 { x: 100.0, y: -20.0 });
+        assert_close((parent * child).transform_point(Vector2 { x: 1.0, y: 0.0 }), Vector2 { x: 100.0, y: -22.0 });
+        // the other way round the parent's turn and scale don't touch the child's offset
+        assert_close((child * parent).translation, Vector2 { x: 110.0, y: 0.0 });
+        // grandchildren compose the same whichever pair goes first
+        let grandchild = transformation(Vector2 { x: 0.0, y: 5.0 }, 30.0, Vector2 { x: 1.0, y: 3.0 }, Vector2 { x: 0.0, y: 0.0 }).matrix();
+        assert_affine_close((parent * child) * grandchild, parent * (child * grandchild));
+    }
+
+    #[test]
+    fn scale_then_skew_then_rotation() {
+        let matrix = transformation(Vector2 { x: 0.0, y: 0.0 }, 90.0, Vector2 { x: 2.0, y: 1.0 }, Vector2 { x: 45.0, y: 0.0 }).matrix();
+        // x is only scaled before turning, (2, 0) turned clockwise points down
+        assert_close(matrix.x_axis, Vector2 { x: 0.0, y: -2.0 });
+        // y leans to (1, 1) first, then turns to (1, -1)
+        assert_close(matrix.y_axis, Vector2 { x: 1.0, y: -1.0 });
+    }
+
+    #[test]
+    fn rotation_turns_around_the_local_origin() {
+        let matrix = transformation(Vector2 { x: 50.0, y: 50.0 }, 180.0, Vector2 { x: 1.0, y: 1.0 }, Vector2 { x: 0.0, y: 0.0 }).matrix();
+        assert_close(matrix.transform_point(Vector2 { x: 0.0, y: 0.0 }), Vector2 { x: 50.0, y: 50.0 });
+        assert_close(matrix.transform_point(Vector2 { x: 10.0, y: 0.0 }), Vector2 { x: 40.0, y: 50.0 });
+        // to turn around some other pivot, move the pivot to the origin first
+        let pivot = Vector2 { x: 10.0, y: 0.0 };
+        let around_pivot = matrix * translation(-pivot.x, -pivot.y);
+        assert_close(around_pivot.transform_point(pivot), Vector2 { x: 50.0, y: 50.0 });
+        assert_close(around_pivot.transform_point(Vector2 { x: 0.0, y: 0.0 }), Vector2 { x: 60.0, y: 50.0 });
     }
 
-    pub fn rotation(&self) -> [[f32; 2]; 2] {
-        self.rotation
+    #[test]
+    fn inverse_undoes_skew_and_uneven_scale() {
+        let matrix = transformation(Vector2 { x: -30.0, y: 12.5 }, 37.0, Vector2 { x: 3.0, y: 0.5 }, Vector2 { x: 20.0, y: -10.0 }).matrix();
+        let inverse = matrix.inverse().unwrap();
+        assert_affine_close(matrix * inverse, Affine2::identity());
+        assert_affine_close(inverse * matrix, Affine2::identity());
+        for point in [Vector2 { x: 0.0, y: 0.0 }, Vector2 { x: 7.0, y: -3.0 }, Vector2 { x: -100.0, y: 40.0 }] {
+            assert_close(inverse.transform_point(matrix.transform_point(point)), point);
+        }
     }
 
-    pub fn scale(&self) -> [[f32; 2]; 2] {
-        self.scale
+    #[test]
+    fn flat_transforms_have_no_inverse() {
+        let matrix = transformation(Vector2 { x: 1.0, y: 1.0 }, 45.0, Vector2 { x: 0.0, y: 2.0 }, Vector2 { x: 0.0, y: 0.0 }).matrix();
+        assert_eq!(matrix.inverse(), None);
     }
 }
@@ -1,14 +1,78 @@
-mod engine;
-mod actors;
-mod scenes;
+pub mod engine;
+pub mod actors;
+pub mod controls;
+pub mod scenes;
+pub use crate::engine::error::EngineError;
+use crate::engine::context::Context;
+use crate::engine::error::Result;
+use crate::engine::game::Game;
 use crate::engine::run::run;
+use crate::engine::sprite_batch::SpriteBatch;
+use crate::scenes::title::TitleScene;
+use crate::scenes::SceneStack;
+use std::time::Duration;
+#[cfg(target_arch = "wasm32")]
+use wasm_bindgen::prelude::*;
+use winit::event::WindowEvent;
 
-struct Shmup {}
+// The shmup itself, all of its gameplay lives in the scenes it pushes.
+pub struct Shmup {
+    scenes: SceneStack,
+}
 
 impl Shmup {
-    fn update() {}
+    pub fn new() -> Self {
+        Shmup {
+            scenes: SceneStack::new(),
+        }
+    }
+}
+
+impl Default for Shmup {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Game for Shmup {
+    fn init(&mut self, ctx: &mut Context) -> Result<()> {
+        // One atlas page for every sprite, so a whole frame of gameplay is a single draw.
+        ctx.assets.pack(&ctx.gpu, &["assets/player.png", "assets/enemy.png", "assets/bullet.png"])?;
+        ctx.input.set_map(controls::load());
+        let title = TitleScene::new(ctx)?;
+        self.scenes.push(Box::new(title), ctx);
+        Ok(())
+    }
+
+    fn update(&mut self, ctx: &mut Context, dt: Duration) -> Result<()> {
+        self.scenes.update(ctx, dt)?;
+        // The game is over once the last scene has been popped.
+        if self.scenes.is_empty() || ctx.input.pressed(controls::QUIT) {
+            ctx.exit();
+        }
+        Ok(())
+    }
+
+    fn render(&mut self, ctx: &Context, alpha: f32, batch: &mut SpriteBatch) {
+        self.scenes.interpolate(alpha);
+        self.scenes.render(ctx, batch);
+    }
+
+    fn input(&mut self, ctx: &mut Context, event: &WindowEvent) -> bool {
+        self.scenes.input(ctx, event)
+    }
+}
+
+// Opens a window and runs the given game until it exits or the window is closed.
+// Only returns if the window or gpu couldn't be set up.
+pub fn run_game<G: Game + 'static>(game: G) -> Result<()> {
+    pollster::block_on(run(game))
 }
 
-pub fn main_loop() {
-    pollster::block_on(run());
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen(start)]
+pub async fn start() {
+    if let Err(err) = run(Shmup::new()).await {
+        log::error!("{}", err);
+    }
 }
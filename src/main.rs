@@ -1,4 +1,8 @@
-use shmup_wgpu_rs::main_loop;
+use shmup_wgpu_rs::run_game;
+use shmup_wgpu_rs::Shmup;
 fn main() {
-    main_loop();
+    if let Err(err) = run_game(Shmup::new()) {
+        eprintln!("error: {}", err);
+        std::process::exit(1);
+    }
 }
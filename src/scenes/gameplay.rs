@@ -0,0 +1,94 @@
+use crate::actors::danmaku::{self, EnemyBullets, Emitters};
+use crate::actors::weapon::{self, Weapon};
+use crate::actors::{self, bullet, enemy, player, GameSprites, PlayerHit};
+use crate::controls;
+use crate::engine::context::Context;
+use crate::engine::draw::Draw;
+use crate::engine::ecs::schedule::{self, Schedule};
+use crate::engine::ecs::{systems, World};
+use crate::engine::error::{EngineError, Result};
+use crate::engine::sprite_batch::SpriteBatch;
+use crate::scenes::overlay::OverlayScene;
+use crate::scenes::{Scene, Transition};
+use cgmath::Vector2;
+use std::rc::Rc;
+use std::time::Duration;
+
+// see assets/weapons.ron
+const STARTING_WEAPON: &str = "vulcan";
+
+pub struct GameplayScene {
+    world: World,
+    schedule: Schedule,
+}
+
+impl GameplayScene {
+    pub fn new(ctx: &mut Context) -> Result<Self> {
+        let sprites = GameSprites {
+            player: ctx.assets.sprite(&ctx.gpu, "assets/player.png")?,
+            enemy: ctx.assets.sprite(&ctx.gpu, "assets/enemy.png")?,
+        };
+        let weapons = weapon::load(weapon::WEAPONS_PATH)?;
+        let def = weapons.get(STARTING_WEAPON).ok_or_else(|| EngineError::Config {
+            path: weapon::WEAPONS_PATH.to_string(),
+            message: format!("no weapon called {}", STARTING_WEAPON),
+        })?;
+        let weapon = Weapon::new(Rc::new(def.clone()), &mut ctx.assets, &ctx.gpu)?;
+        let patterns = danmaku::load(danmaku::PATTERNS_PATH)?;
+        let enemy_bullets = EnemyBullets::new(&patterns, ctx)?;
+        let mut world = World::new();
+        let bounds = Vector2 {
+            x: ctx.width as f32,
+            y: ctx.height as f32,
+        };
+        let ship = player::spawn(&mut world, sprites.player.clone(), sprites.player.clone(), Vector2 { x: 200.0, y: 200.0 }, 0.0, 4.0, bounds);
+        world.insert(ship, weapon);
+        world.insert_resource(sprites);
+        world.insert_resource(Emitters(patterns.emitters.into_values().map(Rc::new).collect()));
+        world.insert_resource(enemy_bullets);
+        // enemies and bullets are registered up front, the systems borrow them before any exist
+        world.register::<enemy::Enemy>();
+        world.register::<bullet::Bullet>();
+        world.register::<danmaku::Emitter>();
+
+        let mut schedule = Schedule::with_engine_systems();
+        schedule.add("player input", schedule::INPUT, player::read_input);
+        schedule.add("steer", schedule::INPUT, player::steer);
+        schedule.add("enemies", schedule::LOGIC, enemy::update);
+        schedule.add("spawn enemies", schedule::LOGIC, enemy::spawn_wave);
+        schedule.add("fire", schedule::LOGIC, weapon::fire);
+        schedule.add("enemy fire", schedule::LOGIC, danmaku::emit);
+        schedule.add("orbit drones", schedule::MOVEMENT + 10, player::orbit_drones);
+        schedule.add("enemy bullets", schedule::MOVEMENT + 10, danmaku::update_bullets);
+        schedule.add("collision events", schedule::COLLISION + 10, actors::collisions());
+        schedule.add("enemy bullet hits", schedule::COLLISION + 10, danmaku::hits);
+        Ok(GameplayScene { world, schedule })
+    }
+}
+
+impl Scene for GameplayScene {
+    fn update(&mut self, ctx: &mut Context, dt: Duration) -> Result<Transition> {
+        if ctx.input.pressed(controls::PAUSE) {
+            return Ok(Transition::Push(Box::new(OverlayScene::pause())));
+        }
+        self.schedule.run(&mut self.world, ctx, dt)?;
+        if self.world.remove_resource::<PlayerHit>().is_some() {
+            return Ok(Transition::Push(Box::new(OverlayScene::game_over())));
+        }
+        Ok(Transition::None)
+    }
+
+    fn interpolate(&mut self, alpha: f32) {
+        systems::interpolate(&mut self.world, alpha);
+        if let Some(mut bullets) = self.world.resource_mut::<EnemyBullets>() {
+            bullets.interpolate(alpha);
+        }
+    }
+
+    fn render(&self, ctx: &Context, batch: &mut SpriteBatch) {
+        systems::render(&self.world, &ctx.gpu, batch);
+        if let Some(bullets) = self.world.resource::<EnemyBullets>() {
+            bullets.draw(&ctx.gpu, batch);
+        }
+    }
+}
@@ -0,0 +1,139 @@
+pub mod title;
+pub mod gameplay;
+pub mod overlay;
+
+use crate::engine::context::Context;
+use crate::engine::error::Result;
+use crate::engine::sprite_batch::SpriteBatch;
+use std::time::Duration;
+use winit::event::WindowEvent;
+
+// What the stack should do after a scene has updated.
+pub enum Transition {
+    None,
+    Push(Box<dyn Scene>),
+    Pop,
+    // pop the current scene and push a new one in its place
+    Switch(Box<dyn Scene>),
+    // clear the whole stack and start again from the given scene
+    Reset(Box<dyn Scene>),
+    Quit,
+}
+
+// A screen of the game (title, gameplay, pause...), the stack owned by State decides which
+// ones get updated and drawn.
+pub trait Scene {
+    fn enter(&mut self, _ctx: &mut Context) {}
+
+    fn exit(&mut self, _ctx: &mut Context) {}
+
+    // Called once per fixed tick, only for the scene on top of the stack.
+    fn update(&mut self, ctx: &mut Context, dt: Duration) -> Result<Transition>;
+
+    // Called once per frame after the ticks have run, see Entity::interpolate.
+    fn interpolate(&mut self, _alpha: f32) {}
+
+    fn render(&self, ctx: &Context, batch: &mut SpriteBatch);
+
+    // Returns true if the event was used, only the top scene gets events.
+    fn input(&mut self, _ctx: &mut Context, _event: &WindowEvent) -> bool {
+        false
+    }
+
+    // Overlays are drawn on top of the scene below them instead of replacing it.
+    fn is_overlay(&self) -> bool {
+        false
+    }
+}
+
+pub struct SceneStack {
+    scenes: Vec<Box<dyn Scene>>,
+}
+
+impl SceneStack {
+    pub fn new() -> Self {
+        SceneStack { scenes: Vec::new() }
+    }
+
+    pub fn push(&mut self, mut scene: Box<dyn Scene>, ctx: &mut Context) {
+        scene.enter(ctx);
+        self.scenes.push(scene);
+    }
+
+    pub fn pop(&mut self, ctx: &mut Context) -> Option<Box<dyn Scene>> {
+        let mut scene = self.scenes.pop()?;
+        scene.exit(ctx);
+        Some(scene)
+    }
+
+    pub fn clear(&mut self, ctx: &mut Context) {
+        while self.pop(ctx).is_some() {}
+    }
+
+    pub fn apply(&mut self, transition: Transition, ctx: &mut Context) {
+        match transition {
+            Transition::None => return,
+            Transition::Push(scene) => self.push(scene, ctx),
+            Transition::Pop => {
+                self.pop(ctx);
+            }
+            Transition::Switch(scene) => {
+                self.pop(ctx);
+                self.push(scene, ctx);
+            }
+            Transition::Reset(scene) => {
+                self.clear(ctx);
+                self.push(scene, ctx);
+            }
+            Transition::Quit => self.clear(ctx),
+        }
+        // Whatever only the scenes that just left were using can go now.
+        ctx.assets.collect_unused();
+    }
+
+    pub fn update(&mut self, ctx: &mut Context, dt: Duration) -> Result<()> {
+        if let Some(scene) = self.scenes.last_mut() {
+            let transition = scene.update(ctx, dt)?;
+            self.apply(transition, ctx);
+        }
+        Ok(())
+    }
+
+    pub fn interpolate(&mut self, alpha: f32) {
+        for scene in &mut self.scenes {
+            scene.interpolate(alpha);
+        }
+    }
+
+    // Draws the top scene and, if it's an overlay, everything underneath it down to the
+    // first scene that isn't one.
+    pub fn render(&self, ctx: &Context, batch: &mut SpriteBatch) {
+        let mut first = self.scenes.len();
+        while first > 0 {
+            first -= 1;
+            if !self.scenes[first].is_overlay() {
+                break;
+            }
+        }
+        for scene in &self.scenes[first..] {
+            scene.render(ctx, batch);
+        }
+    }
+
+    pub fn input(&mut self, ctx: &mut Context, event: &WindowEvent) -> bool {
+        match self.scenes.last_mut() {
+            Some(scene) => scene.input(ctx, event),
+            None => false,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.scenes.is_empty()
+    }
+}
+
+impl Default for SceneStack {
+    fn default() -> Self {
+        Self::new()
+    }
+}
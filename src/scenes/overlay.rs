@@ -0,0 +1,65 @@
+use crate::controls;
+use crate::engine::context::Context;
+use crate::engine::error::Result;
+use crate::engine::sprite_batch::SpriteBatch;
+use crate::scenes::title::TitleScene;
+use crate::scenes::{Scene, Transition};
+use std::time::Duration;
+
+type Action = Box<dyn FnMut(&mut Context) -> Result<Transition>>;
+
+// Sits on top of the gameplay scene, which stays on screen but stops updating, until one of
+// its actions is pressed.
+pub struct OverlayScene {
+    message: &'static str,
+    // checked in order, the first one pressed this tick decides what happens
+    actions: Vec<(&'static str, Action)>,
+}
+
+impl OverlayScene {
+    pub fn new(message: &'static str) -> Self {
+        OverlayScene {
+            message,
+            actions: Vec::new(),
+        }
+    }
+
+    pub fn on(mut self, action: &'static str, handler: impl FnMut(&mut Context) -> Result<Transition> + 'static) -> Self {
+        self.actions.push((action, Box::new(handler)));
+        self
+    }
+
+    // Pausing again resumes.
+    pub fn pause() -> Self {
+        OverlayScene::new("paused").on(controls::PAUSE, |_| Ok(Transition::Pop))
+    }
+
+    // Freezes the last frame of gameplay, confirm goes back to the title.
+    pub fn game_over() -> Self {
+        OverlayScene::new("game over")
+            .on(controls::CONFIRM, |ctx| Ok(Transition::Reset(Box::new(TitleScene::new(ctx)?))))
+    }
+
+    // No text rendering yet, so nothing shows it.
+    pub fn message(&self) -> &str {
+        self.message
+    }
+}
+
+impl Scene for OverlayScene {
+    fn update(&mut self, ctx: &mut Context, _dt: Duration) -> Result<Transition> {
+        for (action, handler) in &mut self.actions {
+            if ctx.input.pressed(action) {
+                return handler(ctx);
+            }
+        }
+        Ok(Transition::None)
+    }
+
+    // nothing of its own to draw, the scene underneath stays on screen
+    fn render(&self, _ctx: &Context, _batch: &mut SpriteBatch) {}
+
+    fn is_overlay(&self) -> bool {
+        true
+    }
+}
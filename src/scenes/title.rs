@@ -0,0 +1,37 @@
+use crate::controls;
+use crate::engine::context::Context;
+use crate::engine::error::Result;
+use crate::engine::sprite_batch::SpriteBatch;
+use crate::engine::draw::Draw;
+use crate::engine::entity::Entity;
+use crate::scenes::gameplay::GameplayScene;
+use crate::scenes::{Scene, Transition};
+use cgmath::Vector2;
+use std::time::Duration;
+
+// No text rendering yet, so the title is just the player ship in the middle of the screen.
+// Confirm starts the game.
+pub struct TitleScene {
+    ship: Entity,
+}
+
+impl TitleScene {
+    pub fn new(ctx: &mut Context) -> Result<Self> {
+        let ship_sprite = ctx.assets.sprite(&ctx.gpu, "assets/player.png")?;
+        let ship = Entity::new(ship_sprite, Vector2 { x: ctx.width as f32 / 2.0, y: ctx.height as f32 / 2.0 }, 0.0, 8.0);
+        Ok(TitleScene { ship })
+    }
+}
+
+impl Scene for TitleScene {
+    fn update(&mut self, ctx: &mut Context, _dt: Duration) -> Result<Transition> {
+        if ctx.input.pressed(controls::CONFIRM) {
+            return Ok(Transition::Switch(Box::new(GameplayScene::new(ctx)?)));
+        }
+        Ok(Transition::None)
+    }
+
+    fn render(&self, ctx: &Context, batch: &mut SpriteBatch) {
+        self.ship.draw(&ctx.gpu, batch);
+    }
+}